@@ -1,9 +1,21 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::thread::sleep;
 
 use super::config::*;
+use crate::deps::DepStatus;
+use crate::doctor::Check;
 use crate::game::*;
+use crate::goldberg::{GameStats, GoldbergProfile};
 use crate::input::*;
 use crate::launch::launch_game;
+use crate::prefix::PrefixInfo;
+use crate::proton::*;
+use crate::runtime::RuntimeRegistry;
+use crate::snapshots::{SnapshotChange, SnapshotInfo};
+use crate::storage::StorageItem;
+use crate::transfer::ProfileArchiveManifest;
+use crate::trust::*;
 use crate::util::*;
 
 use eframe::egui::{self, Key};
@@ -17,10 +29,22 @@ pub enum MenuPage {
     Instances,
 }
 
+#[derive(Eq, PartialEq, Clone, Copy)]
+pub enum ProfileNameAction {
+    Rename,
+    Duplicate,
+}
+
 #[derive(Eq, PartialEq)]
 pub enum SettingsPage {
     General,
     Gamescope,
+    Handlers,
+    Runtimes,
+    Prefixes,
+    Storage,
+    Dependencies,
+    HealthCheck,
 }
 
 pub struct PartyApp {
@@ -36,6 +60,32 @@ pub struct PartyApp {
     pub games: Vec<Game>,
     pub selected_game: usize,
     pub profiles: Vec<String>,
+    // Profile selected on the profiles page, with its settings being edited
+    pub profile_edit: Option<(String, GoldbergProfile)>,
+    // Rename or duplicate of the selected profile, with the name being typed in
+    pub profile_name_edit: Option<(ProfileNameAction, String)>,
+    // Achievements and stats of the selected profile, by game name
+    pub profile_stats: Vec<(String, GameStats)>,
+    // Colour and avatar of each profile, cached so they aren't read from disk every frame
+    pub profile_looks: HashMap<String, (egui::Color32, Option<PathBuf>)>,
+    pub profile_snapshots: Vec<SnapshotInfo>,
+    // Snapshot being compared to the current save, with what changed
+    pub snapshot_diff: Option<(PathBuf, Vec<(String, SnapshotChange)>)>,
+    // Profile archive waiting on the user to decide how to handle a name conflict
+    pub pending_import: Option<(PathBuf, ProfileArchiveManifest)>,
+    // Changes to the game's settings for the next launch only
+    pub launch_overrides: GameOverrides,
+    // Overrides of the selected game by uid, so they aren't read from disk every frame
+    pub game_overrides: Option<(String, GameOverrides)>,
+    // Last Steam interfaces used for the selected game by uid, for the same reason
+    pub game_interfaces: Option<(String, Option<String>)>,
+    pub trusted_keys: Vec<TrustedKey>,
+    pub runtimes: RuntimeRegistry,
+    pub proton_builds: Vec<ProtonBuild>,
+    pub prefixes: Vec<PrefixInfo>,
+    pub storage: Vec<StorageItem>,
+    pub deps: Vec<DepStatus>,
+    pub checks: Vec<Check>,
 
     pub loading_msg: Option<String>,
     pub loading_since: Option<std::time::Instant>,
@@ -65,6 +115,23 @@ impl Default for PartyApp {
             games: scan_all_games(),
             selected_game: 0,
             profiles: Vec::new(),
+            profile_edit: None,
+            profile_name_edit: None,
+            profile_stats: Vec::new(),
+            profile_looks: HashMap::new(),
+            profile_snapshots: Vec::new(),
+            snapshot_diff: None,
+            pending_import: None,
+            launch_overrides: GameOverrides::default(),
+            game_overrides: None,
+            game_interfaces: None,
+            trusted_keys: load_trusted_keys(),
+            runtimes: RuntimeRegistry::load(),
+            proton_builds: scan_proton_builds(),
+            prefixes: Vec::new(),
+            storage: Vec::new(),
+            deps: Vec::new(),
+            checks: Vec::new(),
             loading_msg: None,
             loading_since: None,
             task: None,
@@ -130,6 +197,10 @@ impl eframe::App for PartyApp {
             }
         });
 
+        if let Some(progress) = self.task.as_ref().and_then(|_| take_progress()) {
+            self.loading_msg = Some(progress);
+            self.loading_since = Some(std::time::Instant::now());
+        }
         if let Some(handle) = self.task.take() {
             if handle.is_finished() {
                 let _ = handle.join();
@@ -174,6 +245,8 @@ impl PartyApp {
     where
         F: FnOnce() + Send + 'static,
     {
+        // Drop any progress message left over from a previous task
+        let _ = take_progress();
         self.loading_msg = Some(msg.to_string());
         self.loading_since = Some(std::time::Instant::now());
         self.task = Some(std::thread::spawn(f));
@@ -314,9 +387,19 @@ impl PartyApp {
         let game = cur_game!(self).to_owned();
         let mut instances = self.instances.clone();
         let mut guests = GUEST_NAMES.to_vec();
+        // Kept guests are handed out first, so players can carry on where they left off
+        let mut kept = match self.options.keep_guests {
+            true => scan_guest_profiles(),
+            false => Vec::new(),
+        };
+        guests.retain(|g| !kept.contains(&format!(".{g}")));
 
         for instance in &mut instances {
             if instance.profselection == 0 {
+                if !kept.is_empty() {
+                    instance.profname = kept.remove(0);
+                    continue;
+                }
                 let i = fastrand::usize(..guests.len());
                 instance.profname = format!(".{}", guests[i]);
                 guests.swap_remove(i);
@@ -336,8 +419,15 @@ impl PartyApp {
             })
             .collect();
 
-        let cfg = self.options.clone();
-        let _ = save_cfg(&cfg);
+        let _ = save_cfg(&self.options);
+        // Global settings, then the game's overrides, then what was changed for this launch
+        let mut cfg = self.options.clone();
+        if let Game::HandlerRef(h) = &game {
+            cfg = load_overrides(&h.uid).apply(&cfg);
+        }
+        let cfg = std::mem::take(&mut self.launch_overrides).apply(&cfg);
+        // The launch may generate new interfaces
+        self.game_interfaces = None;
 
         self.cur_page = MenuPage::Home;
         self.spawn_task(
@@ -346,7 +436,10 @@ impl PartyApp {
                 sleep(std::time::Duration::from_secs(2));
                 if let Err(err) = launch_game(&game, &dev_infos, &instances, &cfg) {
                     println!("{}", err);
-                    msg("Launch Error", &format!("{err}"));
+                    msg(
+                        "Launch Error",
+                        &format!("{err}\n\nSettings > Health Check can help find what's missing."),
+                    );
                 }
             },
         );
@@ -1,9 +1,11 @@
 use std::thread::sleep;
 
 use super::config::*;
+use super::widgets::proton_selector;
 use crate::game::*;
 use crate::input::*;
 use crate::launch::launch_game;
+use crate::proton::*;
 use crate::util::*;
 
 use std::path::PathBuf;
@@ -26,6 +28,7 @@ pub struct LightPartyApp {
     pub instances: Vec<Instance>,
     pub instance_add_dev: Option<usize>,
     pub game: Game,
+    pub proton_builds: Vec<ProtonBuild>,
 
     pub loading_msg: Option<String>,
     pub loading_since: Option<std::time::Instant>,
@@ -47,6 +50,7 @@ impl LightPartyApp {
             instance_add_dev: None,
             // Placeholder, user should define this with program args
             game: Game::ExecRef(Executable::new(PathBuf::from(exec), execargs)),
+            proton_builds: scan_proton_builds(),
             loading_msg: None,
             loading_since: None,
             task: None,
@@ -98,6 +102,10 @@ impl eframe::App for LightPartyApp {
             }
         });
 
+        if let Some(progress) = self.task.as_ref().and_then(|_| take_progress()) {
+            self.loading_msg = Some(progress);
+            self.loading_since = Some(std::time::Instant::now());
+        }
         if let Some(handle) = self.task.take() {
             if handle.is_finished() {
                 let _ = handle.join();
@@ -142,6 +150,8 @@ impl LightPartyApp {
     where
         F: FnOnce() + Send + 'static,
     {
+        // Drop any progress message left over from a previous task
+        let _ = take_progress();
         self.loading_msg = Some(msg.to_string());
         self.loading_since = Some(std::time::Instant::now());
         self.task = Some(std::thread::spawn(f));
@@ -257,8 +267,14 @@ impl LightPartyApp {
                 sleep(std::time::Duration::from_secs(2));
                 if let Err(err) = launch_game(&game, &dev_infos, &instances, &cfg) {
                     println!("{}", err);
-                    msg("Launch Error", &format!("{err}"));
+                    msg(
+                        "Launch Error",
+                        &format!("{err}\n\nRun \"partydeck doctor\" to check what's missing."),
+                    );
                 }
+                // Exiting here skips the guest cleanup at the end of main
+                offer_guest_promotions();
+                let _ = remove_guest_profiles();
                 std::process::exit(0);
             },
         );
@@ -388,14 +404,29 @@ impl LightPartyApp {
         });
 
         ui.horizontal(|ui| {
-        let proton_ver_label = ui.label("Proton version");
-        let proton_ver_editbox = ui.add(
-            egui::TextEdit::singleline(&mut self.options.proton_version)
-                .hint_text("GE-Proton"),
-        );
-        if proton_ver_label.hovered() || proton_ver_editbox.hovered() {
-            self.infotext = "Specify a Proton version. This can be a path, e.g. \"/path/to/proton\" or just a name, e.g. \"GE-Proton\" for the latest version of Proton-GE. If left blank, this will default to \"GE-Proton\". If unsure, leave this blank.".to_string();
-        }
+            let proton_ver_label = ui.label("Proton version");
+            let proton_ver_combo = proton_selector(
+                ui,
+                "proton_version",
+                &mut self.options.proton_version,
+                &self.proton_builds,
+                "GE-Proton (default)",
+            );
+            if proton_ver_label.hovered() || proton_ver_combo.hovered() {
+                self.infotext = "Select the Proton build used for Windows games. Builds installed through Steam, in Steam's compatibilitytools.d folder, or downloaded by UMU are listed here. If left on the default, UMU will download and use the latest version of Proton-GE. If unsure, leave this on the default.".to_string();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let extra_env_label = ui.label("Extra environment variables");
+            let extra_env_editbox = ui.add(
+                egui::TextEdit::multiline(&mut self.options.extra_env_vars)
+                    .desired_rows(2)
+                    .hint_text("VAR=value\nVAR2=value with spaces"),
+            );
+            if extra_env_label.hovered() || extra_env_editbox.hovered() {
+                self.infotext = "Environment variables set for every game, one per line. Values can contain spaces. These are applied after the ones set by the game's handler, so they take priority. If unsure, leave this blank.".to_string();
+            }
         });
 
         let render_scale_slider = ui.add(
@@ -420,6 +451,17 @@ impl LightPartyApp {
         if kbm_support_check.hovered() {
             self.infotext = "Runs a custom Gamescope build with support for holding keyboards and mice. If you want to use your own Gamescope installation, uncheck this.".to_string();
         }
+
+        ui.horizontal(|ui| {
+            let extra_gsc_label = ui.label("Extra Gamescope arguments");
+            let extra_gsc_editbox = ui.add(
+                egui::TextEdit::singleline(&mut self.options.extra_gamescope_args)
+                    .hint_text("--force-grab-cursor"),
+            );
+            if extra_gsc_label.hovered() || extra_gsc_editbox.hovered() {
+                self.infotext = "Additional arguments passed to every Gamescope instance, separated by spaces. These are added after the ones set by the game's handler. If unsure, leave this blank.".to_string();
+            }
+        });
     }
 
     pub fn display_page_instances(&mut self, ui: &mut Ui) {
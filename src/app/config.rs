@@ -3,8 +3,10 @@ use crate::paths::*;
 use std::error::Error;
 use std::fs::File;
 use std::io::BufReader;
+use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub enum PadFilterType {
@@ -13,6 +15,14 @@ pub enum PadFilterType {
     OnlySteamInput,
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub enum PrefixMode {
+    #[default]
+    Shared,
+    PerGame,
+    PerProfile,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct PartyConfig {
     pub force_sdl: bool,
@@ -24,6 +34,26 @@ pub struct PartyConfig {
     #[serde(default)]
     pub vertical_two_player: bool,
     pub pad_filter_type: PadFilterType,
+    // Applied on top of what the handler sets, one VAR=value per line
+    #[serde(default)]
+    pub extra_env_vars: String,
+    #[serde(default)]
+    pub extra_gamescope_args: String,
+    #[serde(default)]
+    pub prefix_mode: PrefixMode,
+    // Where dependencies get installed from, keyed by dependency name; empty uses the default
+    #[serde(default)]
+    pub dep_sources: HashMap<String, String>,
+    // Automatic save snapshots of each kind kept per profile and game; 0 turns them off
+    #[serde(default = "default_snapshot_keep")]
+    pub snapshot_keep: u32,
+    // Keeps guest profiles between sessions until PartyDeck is closed
+    #[serde(default)]
+    pub keep_guests: bool,
+}
+
+fn default_snapshot_keep() -> u32 {
+    5
 }
 
 pub fn load_cfg() -> PartyConfig {
@@ -45,6 +75,12 @@ pub fn load_cfg() -> PartyConfig {
         proton_version: "".to_string(),
         vertical_two_player: false,
         pad_filter_type: PadFilterType::NoSteamInput,
+        extra_env_vars: "".to_string(),
+        extra_gamescope_args: "".to_string(),
+        prefix_mode: PrefixMode::Shared,
+        dep_sources: HashMap::new(),
+        snapshot_keep: 5,
+        keep_guests: false,
     }
 }
 
@@ -54,3 +90,136 @@ pub fn save_cfg(config: &PartyConfig) -> Result<(), Box<dyn Error>> {
     serde_json::to_writer_pretty(file, config)?;
     Ok(())
 }
+
+// Settings a game uses instead of the global ones, kept in overrides/<uid>.json. The same is
+// used for changes made for a single launch. Unset values fall through to the layer below.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct GameOverrides {
+    pub render_scale: Option<i32>,
+    pub gamescope_sdl_backend: Option<bool>,
+    pub kbm_support: Option<bool>,
+    pub force_sdl: Option<bool>,
+    pub proton_version: Option<String>,
+    pub vertical_two_player: Option<bool>,
+}
+
+impl GameOverrides {
+    pub fn count(&self) -> usize {
+        [
+            self.render_scale.is_some(),
+            self.gamescope_sdl_backend.is_some(),
+            self.kbm_support.is_some(),
+            self.force_sdl.is_some(),
+            self.proton_version.is_some(),
+            self.vertical_two_player.is_some(),
+        ]
+        .iter()
+        .filter(|set| **set)
+        .count()
+    }
+
+    // The given settings with these overrides on top
+    pub fn apply(&self, config: &PartyConfig) -> PartyConfig {
+        let mut out = config.clone();
+        if let Some(render_scale) = self.render_scale {
+            out.render_scale = render_scale;
+        }
+        if let Some(gamescope_sdl_backend) = self.gamescope_sdl_backend {
+            out.gamescope_sdl_backend = gamescope_sdl_backend;
+        }
+        if let Some(kbm_support) = self.kbm_support {
+            out.kbm_support = kbm_support;
+        }
+        if let Some(force_sdl) = self.force_sdl {
+            out.force_sdl = force_sdl;
+        }
+        if let Some(proton_version) = &self.proton_version {
+            out.proton_version = proton_version.clone();
+        }
+        if let Some(vertical_two_player) = self.vertical_two_player {
+            out.vertical_two_player = vertical_two_player;
+        }
+        out
+    }
+}
+
+fn path_overrides(uid: &str) -> PathBuf {
+    PATH_PARTY.join("overrides").join(format!("{uid}.json"))
+}
+
+pub fn load_overrides(uid: &str) -> GameOverrides {
+    if let Ok(file) = File::open(path_overrides(uid))
+        && let Ok(overrides) = serde_json::from_reader::<_, GameOverrides>(BufReader::new(file))
+    {
+        return overrides;
+    }
+    GameOverrides::default()
+}
+
+// The game's overrides, read from disk only when the cache holds another game's
+pub fn cached_overrides<'a>(
+    cache: &'a mut Option<(String, GameOverrides)>,
+    uid: &str,
+) -> &'a GameOverrides {
+    if cache.as_ref().is_some_and(|(cached, _)| cached != uid) {
+        *cache = None;
+    }
+    &cache
+        .get_or_insert_with(|| (uid.to_string(), load_overrides(uid)))
+        .1
+}
+
+// Games without any overrides don't get a file
+pub fn save_overrides(uid: &str, overrides: &GameOverrides) -> Result<(), Box<dyn Error>> {
+    let path = path_overrides(uid);
+    if overrides.count() == 0 {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+    std::fs::create_dir_all(PATH_PARTY.join("overrides"))?;
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(file, overrides)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_apply_only_set_values() {
+        let global: PartyConfig = serde_json::from_value(serde_json::json!({
+            "force_sdl": false,
+            "render_scale": 100,
+            "enable_kwin_script": true,
+            "gamescope_sdl_backend": true,
+            "kbm_support": true,
+            "proton_version": "",
+            "pad_filter_type": "NoSteamInput",
+        }))
+        .unwrap();
+        let overrides = GameOverrides {
+            render_scale: Some(110),
+            proton_version: Some("GE-Proton9-1".to_string()),
+            ..Default::default()
+        };
+        let cfg = overrides.apply(&global);
+        assert_eq!(cfg.render_scale, 110);
+        assert_eq!(cfg.proton_version, "GE-Proton9-1");
+        assert_eq!(cfg.kbm_support, global.kbm_support);
+        assert_eq!(cfg.vertical_two_player, global.vertical_two_player);
+        assert_eq!(overrides.count(), 2);
+
+        // A launch's changes go on top of the game's
+        let launch = GameOverrides {
+            render_scale: Some(120),
+            ..Default::default()
+        };
+        let cfg = launch.apply(&cfg);
+        assert_eq!(cfg.render_scale, 120);
+        assert_eq!(cfg.proton_version, "GE-Proton9-1");
+    }
+}
@@ -1,13 +1,26 @@
-use super::app::{MenuPage, PartyApp, SettingsPage};
+use super::app::{MenuPage, PartyApp, ProfileNameAction, SettingsPage};
 use super::config::*;
+use super::widgets::{overrides_editor, proton_selector};
+use crate::deps::*;
+use crate::doctor::*;
 use crate::game::Game::*;
+use crate::goldberg::*;
 use crate::input::*;
 use crate::paths::*;
+use crate::prefix::*;
+use crate::runtime::*;
+use crate::savesync::*;
+use crate::snapshots::*;
+use crate::storage::*;
+use crate::transfer::*;
+use crate::trust::*;
 use crate::util::*;
 
 use dialog::DialogBox;
 use eframe::egui::RichText;
 use eframe::egui::{self, Ui};
+use rfd::FileDialog;
+use std::path::PathBuf;
 
 macro_rules! cur_game {
     ($self:expr) => {
@@ -42,12 +55,53 @@ impl PartyApp {
                 SettingsPage::Gamescope,
                 "Gamescope",
             );
+            ui.selectable_value(&mut self.settings_page, SettingsPage::Handlers, "Handlers");
+            ui.selectable_value(&mut self.settings_page, SettingsPage::Runtimes, "Runtimes");
+            // Prefix sizes take a while to add up, so only scan them when the tab is opened
+            if ui
+                .selectable_value(&mut self.settings_page, SettingsPage::Prefixes, "Prefixes")
+                .clicked()
+            {
+                self.prefixes = scan_prefixes();
+            }
+            if ui
+                .selectable_value(&mut self.settings_page, SettingsPage::Storage, "Storage")
+                .clicked()
+            {
+                self.storage = scan_storage(&self.handler_uids());
+            }
+            if ui
+                .selectable_value(
+                    &mut self.settings_page,
+                    SettingsPage::Dependencies,
+                    "Dependencies",
+                )
+                .clicked()
+            {
+                self.deps = DEPENDENCIES.iter().map(dep_status).collect();
+            }
+            if ui
+                .selectable_value(
+                    &mut self.settings_page,
+                    SettingsPage::HealthCheck,
+                    "Health Check",
+                )
+                .clicked()
+            {
+                self.checks = run_checks(&self.options);
+            }
         });
         ui.separator();
 
         match self.settings_page {
             SettingsPage::General => self.display_settings_general(ui),
             SettingsPage::Gamescope => self.display_settings_gamescope(ui),
+            SettingsPage::Handlers => self.display_settings_handlers(ui),
+            SettingsPage::Runtimes => self.display_settings_runtimes(ui),
+            SettingsPage::Prefixes => self.display_settings_prefixes(ui),
+            SettingsPage::Storage => self.display_settings_storage(ui),
+            SettingsPage::Dependencies => self.display_settings_dependencies(ui),
+            SettingsPage::HealthCheck => self.display_settings_health_check(ui),
         }
 
         ui.with_layout(egui::Layout::bottom_up(egui::Align::Center), |ui| {
@@ -67,6 +121,12 @@ impl PartyApp {
                         proton_version: "".to_string(),
                         vertical_two_player: false,
                         pad_filter_type: PadFilterType::NoSteamInput,
+                        extra_env_vars: "".to_string(),
+                        extra_gamescope_args: "".to_string(),
+                        prefix_mode: PrefixMode::Shared,
+                        dep_sources: std::collections::HashMap::new(),
+                        snapshot_keep: 5,
+                        keep_guests: false,
                     };
                     self.input_devices = scan_input_devices(&self.options.pad_filter_type);
                 }
@@ -78,39 +138,647 @@ impl PartyApp {
     pub fn display_page_profiles(&mut self, ui: &mut Ui) {
         ui.heading("Profiles");
         ui.separator();
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| {
+                ui.set_width(160.0);
+                egui::ScrollArea::vertical()
+                    .max_height(ui.available_height() - 32.0)
+                    .auto_shrink(false)
+                    .show(ui, |ui| {
+                        let mut select: Option<String> = None;
+                        for profile in &self.profiles.clone() {
+                            let selected = self
+                                .profile_edit
+                                .as_ref()
+                                .is_some_and(|(name, _)| name == profile);
+                            let (color, _) = self.profile_look(profile);
+                            let label = RichText::new(profile).color(color);
+                            if ui.selectable_label(selected, label).clicked() {
+                                select = Some(profile.clone());
+                            };
+                        }
+                        if let Some(profile) = select {
+                            self.select_profile(&profile);
+                        }
+                    });
+                if ui.button("New").clicked() {
+                    if let Some(name) = dialog::Input::new("Enter name (must be alphanumeric):")
+                        .title("New Profile")
+                        .show()
+                        .expect("Could not display dialog box")
+                    {
+                        if is_valid_profile_name(&name) {
+                            create_profile(&name).unwrap();
+                        } else {
+                            msg("Error", "Invalid name");
+                        }
+                    }
+                    self.profiles = scan_profiles(false);
+                }
+                let import_btn = ui.button("Import");
+                if import_btn.hovered() {
+                    self.infotext = "Import a profile or game save exported from PartyDeck (.pdp), e.g. on another computer or Steam Deck.".to_string();
+                }
+                if import_btn.clicked()
+                    && let Some(file) = FileDialog::new()
+                        .set_title("Select profile archive")
+                        .add_filter("PartyDeck Profile", &["pdp"])
+                        .set_directory(&*PATH_HOME)
+                        .pick_file()
+                {
+                    match read_archive_manifest(&file) {
+                        Ok(manifest) if self.profiles.contains(&manifest.profile) => {
+                            self.pending_import = Some((file, manifest));
+                        }
+                        Ok(manifest) => {
+                            self.finish_import(&file, ImportMode::Merge, &manifest.profile)
+                        }
+                        Err(err) => msg("Error", &format!("Couldn't read archive: {}", err)),
+                    }
+                }
+            });
+            ui.separator();
+            ui.vertical(|ui| self.display_profile_settings(ui));
+        });
+
+        self.display_import_conflict(ui);
+    }
+
+    fn finish_import(&mut self, file: &PathBuf, mode: ImportMode, name: &str) {
+        match import_profile(file, mode, name) {
+            Ok(()) => {
+                self.profiles = scan_profiles(false);
+                self.profile_looks.clear();
+                self.select_profile(name);
+            }
+            Err(err) => msg("Error", &format!("Couldn't import profile: {}", err)),
+        }
+    }
+
+    // Asks what to do when an imported profile has the same name as an existing one
+    fn display_import_conflict(&mut self, ui: &mut Ui) {
+        let Some((file, manifest)) = self.pending_import.clone() else {
+            return;
+        };
+        let what = match manifest.is_whole_profile() {
+            true => "profile".to_string(),
+            false => format!("save for {}", manifest.games.join(", ")),
+        };
+
+        let mut choice: Option<ImportMode> = None;
+        let mut cancel = false;
+        egui::Window::new("Import Profile")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ui.ctx(), |ui| {
+                ui.label(format!(
+                    "A profile named {} already exists. What should be done with the imported {what}?",
+                    manifest.profile
+                ));
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Merge")
+                        .on_hover_text("Add the imported saves to the existing profile, overwriting files that exist in both")
+                        .clicked()
+                    {
+                        choice = Some(ImportMode::Merge);
+                    }
+                    if ui
+                        .button("Replace")
+                        .on_hover_text("Erase what the existing profile has and use the imported data instead")
+                        .clicked()
+                    {
+                        choice = Some(ImportMode::Replace);
+                    }
+                    if ui
+                        .button("Rename")
+                        .on_hover_text("Import as a new profile with a different name")
+                        .clicked()
+                    {
+                        choice = Some(ImportMode::Rename);
+                    }
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        let name = match choice {
+            Some(ImportMode::Rename) => {
+                match Self::ask_profile_name(
+                    "Import Profile",
+                    "Enter a name for the imported profile (must be alphanumeric):",
+                ) {
+                    Some(name) => name,
+                    None => return,
+                }
+            }
+            Some(_) => manifest.profile.clone(),
+            None => {
+                if cancel {
+                    self.pending_import = None;
+                }
+                return;
+            }
+        };
+        self.pending_import = None;
+        self.finish_import(&file, choice.unwrap(), &name);
+    }
+
+    // Colour and avatar shown for a profile around the GUI
+    pub fn profile_look(&mut self, profname: &str) -> (egui::Color32, Option<PathBuf>) {
+        if profname == "Guest" {
+            return (egui::Color32::GRAY, None);
+        }
+        self.profile_looks
+            .entry(profname.to_string())
+            .or_insert_with(|| {
+                let [r, g, b] = ProfileMeta::load(profname).color;
+                (egui::Color32::from_rgb(r, g, b), profile_avatar(profname))
+            })
+            .clone()
+    }
+
+    fn select_profile(&mut self, profname: &str) {
+        self.profile_edit = Some((profname.to_string(), GoldbergProfile::load(profname)));
+        self.profile_name_edit = None;
+        self.profile_snapshots = list_snapshots(profname);
+        self.snapshot_diff = None;
+        self.profile_stats = self
+            .games
+            .iter()
+            .filter_map(|g| match g {
+                HandlerRef(h) => {
+                    load_game_stats(profname, h).map(|stats| (h.display().to_string(), stats))
+                }
+                ExecRef(_) => None,
+            })
+            .collect();
+    }
+
+    fn ask_profile_name(title: &str, prompt: &str) -> Option<String> {
+        let name = dialog::Input::new(prompt)
+            .title(title)
+            .show()
+            .expect("Could not display dialog box")?;
+        if !is_valid_profile_name(&name) {
+            msg("Error", "Invalid name");
+            return None;
+        }
+        Some(name)
+    }
+
+    fn display_profile_settings(&mut self, ui: &mut Ui) {
+        let Some((profname, _)) = &self.profile_edit else {
+            ui.label("Select a profile to edit its settings.");
+            return;
+        };
+        let profname = profname.clone();
+        let (color, avatar) = self.profile_look(&profname);
+
+        // Renaming, duplicating and deleting change the profile list, so they happen after drawing
+        let mut export: Option<Option<usize>> = None;
+        let mut rename = false;
+        let mut duplicate = false;
+        let mut delete = false;
+        ui.horizontal(|ui| {
+            if let Some(avatar) = avatar {
+                ui.add(
+                    egui::Image::new(format!("file://{}", avatar.display()))
+                        .max_width(64.0)
+                        .max_height(64.0),
+                );
+            }
+            ui.heading(RichText::new(&profname).color(color));
+            rename = ui.button("Rename").clicked();
+            duplicate = ui.button("Duplicate").clicked();
+            delete = ui.button("Delete").clicked();
+        });
+
+        if rename {
+            self.profile_name_edit = Some((ProfileNameAction::Rename, profname.clone()));
+        } else if duplicate {
+            self.profile_name_edit = Some((ProfileNameAction::Duplicate, String::new()));
+        }
+        let mut name_done = false;
+        let mut name_cancel = false;
+        if let Some((action, name)) = &mut self.profile_name_edit {
+            ui.horizontal(|ui| {
+                ui.label(match action {
+                    ProfileNameAction::Rename => "New name",
+                    ProfileNameAction::Duplicate => "Name of the copy",
+                });
+                let name_edit =
+                    ui.add(egui::TextEdit::singleline(name).hint_text("Letters and numbers only"));
+                // Focused right away so typing works without clicking, and Enter (the gamepad's
+                // A button) confirms
+                if rename || duplicate {
+                    name_edit.request_focus();
+                }
+                if name_edit.lost_focus() {
+                    name_done = ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    name_cancel = ui.input(|i| i.key_pressed(egui::Key::Escape));
+                }
+                name_done |= ui.button("OK").clicked();
+                name_cancel |= ui.button("Cancel").clicked();
+            });
+        }
+
+        ui.horizontal(|ui| {
+            let color_label = ui.label("Colour");
+            if color_label.hovered() {
+                self.infotext =
+                    "Marks the profile's name in the profile list and on the instances page."
+                        .to_string();
+            }
+            for [r, g, b] in PROFILE_COLORS {
+                let swatch = egui::Color32::from_rgb(r, g, b);
+                let text = match swatch == color {
+                    true => "✔",
+                    false => "",
+                };
+                let swatch_btn = egui::Button::new(RichText::new(text).color(egui::Color32::BLACK))
+                    .fill(swatch)
+                    .min_size(egui::vec2(20.0, 20.0));
+                if ui.add(swatch_btn).clicked() {
+                    let meta = ProfileMeta { color: [r, g, b] };
+                    if let Err(err) = meta.save(&profname) {
+                        msg("Error", &format!("Couldn't save profile: {}", err));
+                    }
+                    self.profile_looks.remove(&profname);
+                }
+            }
+        });
+
+        let Some((_, profile)) = &mut self.profile_edit else {
+            return;
+        };
+        let profname = &profname;
+
+        ui.horizontal(|ui| {
+            let name_label = ui.label("Name in game");
+            let name_editbox = ui.text_edit_singleline(&mut profile.name);
+            if name_label.hovered() || name_editbox.hovered() {
+                self.infotext =
+                    "The name other players see for this profile in Steam games.".to_string();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Steam ID");
+            ui.label(RichText::new(&profile.steam_id).weak());
+            let import_btn = ui.button("Import");
+            if import_btn.hovered() {
+                self.infotext = "Use your real Steam ID for this profile, e.g. so games find save files made on Steam. Accepts a SteamID64 (7656119...) or a SteamID3 ([U:1:...]).".to_string();
+            }
+            if import_btn.clicked()
+                && let Some(input) = dialog::Input::new("Enter a SteamID64 or SteamID3:")
+                    .title("Import Steam ID")
+                    .show()
+                    .expect("Could not display dialog box")
+            {
+                match parse_steam_id(&input) {
+                    Some(id) => profile.steam_id = id,
+                    None => msg("Error", "Invalid Steam ID"),
+                }
+            }
+            if profile.steam_id != steam_id_for_name(profname)
+                && ui.button("Reset").on_hover_text("Go back to the generated ID").clicked()
+            {
+                profile.steam_id = unique_steam_id(profname);
+            }
+        });
+        ui.horizontal(|ui| {
+            let lang_label = ui.label("Language");
+            let lang_combo = egui::ComboBox::from_id_salt("profile_language")
+                .selected_text(&profile.language)
+                .show_ui(ui, |ui| {
+                    for lang in LANGUAGES {
+                        ui.selectable_value(&mut profile.language, lang.to_string(), lang);
+                    }
+                });
+            if lang_label.hovered() || lang_combo.response.hovered() {
+                self.infotext =
+                    "The language Steam games will use for this profile, if they support it."
+                        .to_string();
+            }
+        });
+        ui.horizontal(|ui| {
+            let country_label = ui.label("Country");
+            let country_editbox =
+                ui.add(egui::TextEdit::singleline(&mut profile.country).desired_width(32.0));
+            if country_label.hovered() || country_editbox.hovered() {
+                self.infotext =
+                    "Two-letter country code reported to Steam games, e.g. US or GB.".to_string();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                profile.country = profile
+                    .country
+                    .chars()
+                    .filter(|c| c.is_ascii_alphabetic())
+                    .take(2)
+                    .collect::<String>()
+                    .to_uppercase();
+                profile.name = profile.name.replace(['\n', '\r'], "");
+                if let Err(err) = profile.save(profname) {
+                    msg("Error", &format!("Couldn't save profile: {}", err));
+                }
+            }
+            if ui.button("Change Avatar").clicked()
+                && let Some(file) = FileDialog::new()
+                    .set_title("Select avatar image")
+                    .add_filter("Images", &["png", "jpg", "jpeg"])
+                    .set_directory(&*PATH_HOME)
+                    .pick_file()
+            {
+                if let Some(old) = profile_avatar(profname) {
+                    ui.ctx().forget_image(&format!("file://{}", old.display()));
+                }
+                if let Err(err) = set_profile_avatar(profname, &file) {
+                    msg("Error", &format!("Couldn't set avatar: {}", err));
+                }
+                self.profile_looks.remove(profname);
+            }
+            ui.menu_button("Export", |ui| {
+                if ui.button("Whole profile").clicked() {
+                    export = Some(None);
+                    ui.close_menu();
+                }
+                for (gi, game) in self.games.iter().enumerate() {
+                    let HandlerRef(h) = game else {
+                        continue;
+                    };
+                    let path_save = PATH_PARTY
+                        .join("profiles")
+                        .join(profname)
+                        .join("saves")
+                        .join(&h.uid);
+                    if path_save.exists() && ui.button(h.display()).clicked() {
+                        export = Some(Some(gi));
+                        ui.close_menu();
+                    }
+                }
+            });
+            if ui.button("Open Folder").clicked()
+                && std::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(format!(
+                        "xdg-open \"{}/profiles/{}\"",
+                        PATH_PARTY.display(),
+                        profname
+                    ))
+                    .status()
+                    .is_err()
+            {
+                msg("Error", "Couldn't open profile directory!");
+            }
+        });
+
+        ui.separator();
+        ui.collapsing("Save snapshots", |ui| {
+            self.display_profile_snapshots(ui, profname)
+        });
+
+        ui.separator();
+        ui.label(RichText::new("Achievements and stats").strong());
+        if self.profile_stats.is_empty() {
+            ui.label("No Steam games played with this profile yet.");
+        }
         egui::ScrollArea::vertical()
             .max_height(ui.available_height() - 16.0)
             .auto_shrink(false)
             .show(ui, |ui| {
-                for profile in &self.profiles {
-                    if ui.selectable_value(&mut 0, 0, profile).clicked() {
-                        if let Err(_) = std::process::Command::new("sh")
-                            .arg("-c")
-                            .arg(format!(
-                                "xdg-open {}/profiles/{}",
-                                PATH_PARTY.display(),
-                                profile
-                            ))
-                            .status()
-                        {
-                            msg("Error", "Couldn't open profile directory!");
-                        }
+                for (game, stats) in &self.profile_stats {
+                    let earned = stats.achievements.iter().filter(|a| a.earned).count();
+                    let header = match stats.achievements.is_empty() {
+                        true => game.clone(),
+                        false => format!("{game} ({earned}/{})", stats.achievements.len()),
                     };
+                    ui.collapsing(header, |ui| {
+                        for ach in &stats.achievements {
+                            ui.horizontal(|ui| {
+                                let (icon, color) = match ach.earned {
+                                    true => ("✔", egui::Color32::LIGHT_GREEN),
+                                    false => ("✖", egui::Color32::GRAY),
+                                };
+                                ui.label(RichText::new(icon).color(color));
+                                ui.label(&ach.display_name);
+                                ui.label(RichText::new(&ach.description).small().weak());
+                                if ach.earned && ach.earned_time > 0 {
+                                    ui.label(
+                                        RichText::new(time_ago(ach.earned_time)).small().weak(),
+                                    );
+                                }
+                            });
+                        }
+                        if !stats.stats.is_empty() {
+                            ui.add_space(4.0);
+                            egui::Grid::new(format!("stats_{game}")).show(ui, |ui| {
+                                for (name, value) in &stats.stats {
+                                    ui.label(name);
+                                    ui.label(value);
+                                    ui.end_row();
+                                }
+                            });
+                        }
+                    });
                 }
             });
-        if ui.button("New").clicked() {
-            if let Some(name) = dialog::Input::new("Enter name (must be alphanumeric):")
-                .title("New Profile")
-                .show()
-                .expect("Could not display dialog box")
+
+        if let Some(game) = export {
+            let h = game.and_then(|gi| match &self.games[gi] {
+                HandlerRef(h) => Some(h),
+                ExecRef(_) => None,
+            });
+            let filename = match h {
+                Some(h) => format!("{profname}-{}.pdp", h.uid),
+                None => format!("{profname}.pdp"),
+            };
+            if let Some(dest) = FileDialog::new()
+                .set_title("Export profile")
+                .add_filter("PartyDeck Profile", &["pdp"])
+                .set_directory(&*PATH_HOME)
+                .set_file_name(filename)
+                .save_file()
+                && let Err(err) = export_profile(profname, h, &dest)
             {
-                if !name.is_empty() && name.chars().all(char::is_alphanumeric) {
-                    create_profile(&name).unwrap();
-                } else {
-                    msg("Error", "Invalid name");
+                msg("Error", &format!("Couldn't export profile: {}", err));
+            }
+        }
+
+        if name_cancel {
+            self.profile_name_edit = None;
+        } else if name_done && let Some((action, new)) = self.profile_name_edit.clone() {
+            let result = match is_valid_profile_name(&new) {
+                false => Err("Invalid name".into()),
+                true if action == ProfileNameAction::Rename => rename_profile(profname, &new),
+                true => duplicate_profile(profname, &new),
+            };
+            match result {
+                Ok(()) => {
+                    self.profiles = scan_profiles(false);
+                    self.profile_looks.clear();
+                    self.select_profile(&new);
                 }
+                Err(err) => msg("Error", &format!("Couldn't save profile: {}", err)),
+            }
+        } else if delete
+            && yesno(
+                "Delete Profile?",
+                &format!(
+                    "This will erase {profname} along with all of its save data, snapshots, settings and stats. Are you sure?"
+                ),
+            )
+        {
+            if let Err(err) = delete_profile(profname) {
+                msg("Error", &format!("Couldn't delete profile: {}", err));
             }
             self.profiles = scan_profiles(false);
+            self.profile_looks.clear();
+            self.profile_edit = None;
+            self.profile_stats.clear();
+        }
+    }
+
+    fn display_profile_snapshots(&mut self, ui: &mut Ui, profname: &str) {
+        // (game index, label) to snapshot, or (snapshot index, action) for an existing one
+        let mut take: Option<(usize, String)> = None;
+        let mut action: Option<(usize, SnapshotAction)> = None;
+
+        let mut any = false;
+        for (gi, game) in self.games.iter().enumerate() {
+            let HandlerRef(h) = game else {
+                continue;
+            };
+            let has_save = PATH_PARTY
+                .join("profiles")
+                .join(profname)
+                .join("saves")
+                .join(&h.uid)
+                .exists();
+            if !has_save && !self.profile_snapshots.iter().any(|s| s.uid == h.uid) {
+                continue;
+            }
+            any = true;
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(h.display()).strong());
+                if ui.button("Take Snapshot").clicked()
+                    && let Some(label) = dialog::Input::new("Enter a label for the snapshot:")
+                        .title("Take Snapshot")
+                        .show()
+                        .expect("Could not display dialog box")
+                {
+                    take = Some((gi, label));
+                }
+            });
+            for (si, snapshot) in self.profile_snapshots.iter().enumerate() {
+                if snapshot.uid != h.uid {
+                    continue;
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("🗑").clicked() {
+                        action = Some((si, SnapshotAction::Delete));
+                    }
+                    let label = match snapshot.kind {
+                        _ if !snapshot.label.is_empty() => snapshot.label.as_str(),
+                        SnapshotKind::Manual => "Manual",
+                        SnapshotKind::Launch => "Before launch",
+                        SnapshotKind::Restore => "Before restore",
+                        SnapshotKind::Import => "Before import",
+                        SnapshotKind::Export => "Steam save before export",
+                    };
+                    ui.label(label);
+                    ui.label(RichText::new(time_ago(snapshot.created)).small().weak());
+                    ui.label(RichText::new(human_size(snapshot.size)).small().weak());
+                    if ui.button("Compare").clicked() {
+                        action = Some((si, SnapshotAction::Compare));
+                    }
+                    if ui.button("Restore").clicked() {
+                        action = Some((si, SnapshotAction::Restore));
+                    }
+                });
+                if let Some((path, changes)) = &self.snapshot_diff {
+                    if *path != snapshot.path {
+                        continue;
+                    }
+                    if changes.is_empty() {
+                        ui.label(RichText::new("Same as the current save").small());
+                    }
+                    for (file, change) in changes {
+                        let (text, color) = match change {
+                            SnapshotChange::Removed => ("restored", egui::Color32::LIGHT_GREEN),
+                            SnapshotChange::Added => ("removed", egui::Color32::LIGHT_RED),
+                            SnapshotChange::Changed => ("reverted", egui::Color32::YELLOW),
+                        };
+                        ui.horizontal(|ui| {
+                            ui.label("  ");
+                            ui.label(RichText::new(text).small().color(color));
+                            ui.label(RichText::new(file).small());
+                        });
+                    }
+                }
+            }
+            ui.add_space(4.0);
+        }
+        if !any {
+            ui.label("No games played with this profile yet.");
+        }
+
+        if let Some((gi, label)) = take {
+            if let HandlerRef(h) = &self.games[gi] {
+                match create_snapshot(profname, h, &label, SnapshotKind::Manual) {
+                    Ok(Some(_)) => {}
+                    Ok(None) => msg("Take Snapshot", "There's no save data to snapshot yet."),
+                    Err(err) => msg("Error", &format!("Couldn't take snapshot: {}", err)),
+                }
+            }
+            self.profile_snapshots = list_snapshots(profname);
+        }
+
+        let Some((si, action)) = action else {
+            return;
+        };
+        let snapshot = self.profile_snapshots[si].clone();
+        let handler = self.games.iter().find_map(|g| match g {
+            HandlerRef(h) if h.uid == snapshot.uid => Some(h),
+            _ => None,
+        });
+        match action {
+            SnapshotAction::Compare => match diff_snapshot(&snapshot) {
+                Ok(changes) => self.snapshot_diff = Some((snapshot.path.clone(), changes)),
+                Err(err) => msg("Error", &format!("Couldn't read snapshot: {}", err)),
+            },
+            SnapshotAction::Restore => {
+                let Some(h) = handler else {
+                    return;
+                };
+                if yesno(
+                    "Restore Snapshot?",
+                    &format!(
+                        "This will replace {profname}'s current save for {} with the snapshot. The current save will be kept as a snapshot of its own. Continue?",
+                        h.display()
+                    ),
+                ) {
+                    if let Err(err) = restore_snapshot(&snapshot, h) {
+                        msg("Error", &format!("Couldn't restore snapshot: {}", err));
+                    }
+                    self.profile_snapshots = list_snapshots(profname);
+                    self.snapshot_diff = None;
+                }
+            }
+            SnapshotAction::Delete => {
+                if yesno(
+                    "Delete Snapshot?",
+                    "Are you sure you want to delete this snapshot?",
+                ) {
+                    if let Err(err) = delete_snapshot(&snapshot) {
+                        msg("Error", &format!("Couldn't delete snapshot: {}", err));
+                    }
+                    self.profile_snapshots = list_snapshots(profname);
+                }
+            }
         }
     }
 
@@ -132,6 +800,7 @@ impl PartyApp {
             );
             if ui.button("Play").clicked() {
                 self.instances.clear();
+                self.launch_overrides = GameOverrides::default();
                 self.profiles = scan_profiles(true);
                 self.instance_add_dev = None;
                 self.cur_page = MenuPage::Instances;
@@ -142,15 +811,156 @@ impl PartyApp {
                     ui.label(" Proton");
                 } else {
                     ui.label("🐧 Native");
+                    if let Err(err) = self.runtimes.resolve(&h.runtime) {
+                        let (color, note) = match is_steam_runtime(&h.runtime) {
+                            true => (egui::Color32::RED, "The game can't be launched until it's installed."),
+                            false => (egui::Color32::YELLOW, "Until it's installed, the game runs without a runtime."),
+                        };
+                        ui.label(RichText::new("⚠ Runtime missing").color(color))
+                            .on_hover_text(format!("{err}\n\n{note}"));
+                    }
                 }
                 ui.add(egui::Separator::default().vertical());
                 ui.label(format!("Author: {}", h.author));
                 ui.add(egui::Separator::default().vertical());
                 ui.label(format!("Version: {}", h.version));
+                ui.add(egui::Separator::default().vertical());
+                let trust_label = match &h.trust {
+                    HandlerTrust::Trusted(_) => ui.label(h.trust.badge()),
+                    HandlerTrust::Invalid | HandlerTrust::Modified => {
+                        ui.label(RichText::new(h.trust.badge()).color(egui::Color32::RED))
+                    }
+                    _ => ui.label(RichText::new(h.trust.badge()).color(egui::Color32::YELLOW)),
+                };
+                trust_label.on_hover_text("Handlers can run programs alongside your games. Signed handlers from authors in your trusted keys list (Settings → Handlers) are verified to be unmodified.");
             }
         });
 
         if let HandlerRef(h) = cur_game!(self) {
+            let before = cached_overrides(&mut self.game_overrides, &h.uid).clone();
+            let mut overrides = before.clone();
+            let header = match overrides.count() {
+                0 => "Game settings".to_string(),
+                n => format!("Game settings ({n} overridden)"),
+            };
+            egui::CollapsingHeader::new(header)
+                .id_salt("game_overrides")
+                .show(ui, |ui| {
+                    if let Some(text) = overrides_editor(
+                        ui,
+                        "game_overrides",
+                        &mut overrides,
+                        &self.options,
+                        "Global",
+                        &self.proton_builds,
+                        h.win,
+                    ) {
+                        self.infotext = text.to_string();
+                    }
+                });
+            if overrides != before {
+                match save_overrides(&h.uid, &overrides) {
+                    Ok(()) => self.game_overrides = Some((h.uid.clone(), overrides)),
+                    Err(e) => {
+                        msg("Error", &format!("Couldn't save game settings: {}", e));
+                        self.game_overrides = None;
+                    }
+                }
+            }
+            // (profile, export) picked from the menus below
+            let mut sync: Option<(String, bool)> = None;
+            ui.horizontal(|ui| {
+                let import_menu = ui.menu_button("Import existing save", |ui| {
+                    for profname in scan_profiles(false) {
+                        if ui.button(&profname).clicked() {
+                            sync = Some((profname, false));
+                            ui.close_menu();
+                        }
+                    }
+                });
+                if import_menu.response.hovered() {
+                    self.infotext = "Copy your progress from playing the game normally through Steam into a profile. The profile's current save for the game is snapshotted first.".to_string();
+                }
+                let export_menu = ui.menu_button("Export back to Steam", |ui| {
+                    for profname in scan_profiles(false) {
+                        let path_save = PATH_PARTY
+                            .join("profiles")
+                            .join(&profname)
+                            .join("saves")
+                            .join(&h.uid);
+                        if path_save.exists() && ui.button(&profname).clicked() {
+                            sync = Some((profname, true));
+                            ui.close_menu();
+                        }
+                    }
+                });
+                if export_menu.response.hovered() {
+                    self.infotext = "Copy a profile's progress to where the game keeps it when played normally through Steam.".to_string();
+                }
+            });
+            match sync {
+                Some((profname, false)) => match import_existing_save(&profname, h) {
+                    Ok(notes) => {
+                        let mut text = format!("Imported the existing save into {profname}.");
+                        for note in notes {
+                            text.push_str(&format!("\n\n{note}"));
+                        }
+                        msg("Import existing save", &text);
+                    }
+                    Err(err) => msg("Error", &format!("Couldn't import save: {}", err)),
+                },
+                Some((profname, true))
+                    if yesno(
+                        "Export back to Steam?",
+                        &format!(
+                            "This will replace your regular save for {} with {profname}'s; the regular save is kept as one of {profname}'s snapshots first. If the game uses Steam Cloud, Steam may ask which save to keep the next time you play. Are you sure?",
+                            h.display()
+                        ),
+                    ) =>
+                {
+                    match export_save_to_steam(&profname, h, self.options.snapshot_keep as usize) {
+                        Ok(notes) => {
+                            let mut text = format!("Exported {profname}'s save.");
+                            for note in notes {
+                                text.push_str(&format!("\n\n{note}"));
+                            }
+                            msg("Export back to Steam", &text);
+                        }
+                        Err(err) => msg("Error", &format!("Couldn't export save: {}", err)),
+                    }
+                }
+                _ => {}
+            }
+            if !h.path_goldberg.is_empty() && !h.coldclient {
+                if self
+                    .game_interfaces
+                    .as_ref()
+                    .is_none_or(|(uid, _)| *uid != h.uid)
+                {
+                    self.game_interfaces = Some((h.uid.clone(), cached_interfaces(&h.uid)));
+                }
+                if let Some((_, Some(interfaces))) = &self.game_interfaces {
+                    ui.collapsing("Steam interfaces", |ui| {
+                        egui::ScrollArea::vertical()
+                            .id_salt("steam_interfaces")
+                            .max_height(160.0)
+                            .show(ui, |ui| {
+                                ui.label(RichText::new(interfaces).monospace().small());
+                            });
+                    });
+                }
+            }
+            if h.resolved_json.get("handler.extends").is_some() {
+                ui.collapsing("Resolved handler", |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            let text =
+                                serde_json::to_string_pretty(&h.resolved_json).unwrap_or_default();
+                            ui.label(RichText::new(text).monospace().small());
+                        });
+                });
+            }
             egui::ScrollArea::horizontal()
                 .max_width(f32::INFINITY)
                 .show(ui, |ui| {
@@ -216,19 +1026,48 @@ impl PartyApp {
 
         ui.separator();
 
+        let looks: Vec<(egui::Color32, Option<PathBuf>)> = self
+            .profiles
+            .clone()
+            .iter()
+            .map(|p| self.profile_look(p))
+            .collect();
+        let avatar_image = |path: &PathBuf| {
+            egui::Image::new(format!("file://{}", path.display()))
+                .max_width(16.0)
+                .max_height(16.0)
+        };
+
         let mut devices_to_remove = Vec::new();
         for (i, instance) in &mut self.instances.iter_mut().enumerate() {
             ui.horizontal(|ui| {
                 ui.label(format!("Instance {}", i + 1));
 
                 if let HandlerRef(_) = cur_game!(self) {
-                    ui.label("👤");
-                    egui::ComboBox::from_id_salt(format!("{i}")).show_index(
-                        ui,
-                        &mut instance.profselection,
-                        self.profiles.len(),
-                        |i| self.profiles[i].clone(),
-                    );
+                    let (color, avatar) = &looks[instance.profselection];
+                    match avatar {
+                        Some(path) => ui.add(avatar_image(path)),
+                        None => ui.label("👤"),
+                    };
+                    egui::ComboBox::from_id_salt(format!("{i}"))
+                        .selected_text(
+                            RichText::new(&self.profiles[instance.profselection]).color(*color),
+                        )
+                        .show_ui(ui, |ui| {
+                            for (j, profile) in self.profiles.iter().enumerate() {
+                                let (color, avatar) = &looks[j];
+                                ui.horizontal(|ui| {
+                                    if let Some(path) = avatar {
+                                        ui.add(avatar_image(path));
+                                    }
+                                    ui.selectable_value(
+                                        &mut instance.profselection,
+                                        j,
+                                        RichText::new(profile).color(*color),
+                                    );
+                                });
+                            }
+                        });
                 }
 
                 if self.instance_add_dev == None {
@@ -269,6 +1108,35 @@ impl PartyApp {
 
         if self.instances.len() > 0 {
             ui.separator();
+            let (base, win) = match cur_game!(self) {
+                HandlerRef(h) => (
+                    cached_overrides(&mut self.game_overrides, &h.uid).apply(&self.options),
+                    h.win,
+                ),
+                ExecRef(e) => (
+                    self.options.clone(),
+                    e.path().extension().unwrap_or_default() == "exe",
+                ),
+            };
+            let header = match self.launch_overrides.count() {
+                0 => "Settings for this launch".to_string(),
+                n => format!("Settings for this launch ({n} changed)"),
+            };
+            egui::CollapsingHeader::new(header)
+                .id_salt("launch_overrides")
+                .show(ui, |ui| {
+                    if let Some(text) = overrides_editor(
+                        ui,
+                        "launch_overrides",
+                        &mut self.launch_overrides,
+                        &base,
+                        "Game",
+                        &self.proton_builds,
+                        win,
+                    ) {
+                        self.infotext = text.to_string();
+                    }
+                });
             ui.horizontal(|ui| {
                 ui.add(
                     egui::Image::new(egui::include_image!("../../res/BTN_START.png"))
@@ -340,47 +1208,48 @@ impl PartyApp {
         });
 
         ui.horizontal(|ui| {
-        let proton_ver_label = ui.label("Proton version");
-        let proton_ver_editbox = ui.add(
-            egui::TextEdit::singleline(&mut self.options.proton_version)
-                .hint_text("GE-Proton"),
-        );
-        if proton_ver_label.hovered() || proton_ver_editbox.hovered() {
-            self.infotext = "Specify a Proton version. This can be a path, e.g. \"/path/to/proton\" or just a name, e.g. \"GE-Proton\" for the latest version of Proton-GE. If left blank, this will default to \"GE-Proton\". If unsure, leave this blank.".to_string();
-        }
+            let proton_ver_label = ui.label("Proton version");
+            let proton_ver_combo = proton_selector(
+                ui,
+                "proton_version",
+                &mut self.options.proton_version,
+                &self.proton_builds,
+                "GE-Proton (default)",
+            );
+            if proton_ver_label.hovered() || proton_ver_combo.hovered() {
+                self.infotext = "Select the Proton build used for Windows games. Builds installed through Steam, in Steam's compatibilitytools.d folder, or downloaded by UMU are listed here. If left on the default, UMU will download and use the latest version of Proton-GE. If unsure, leave this on the default.".to_string();
+            }
         });
 
-        ui.separator();
-
         ui.horizontal(|ui| {
-        if ui.button("Erase Proton Prefix").clicked() {
-            if yesno("Erase Prefix?", "This will erase the Wine prefix used by PartyDeck. This shouldn't erase profile/game-specific data, but exercise caution. Are you sure?") && PATH_PARTY.join("gamesyms").exists() {
-                if let Err(err) = std::fs::remove_dir_all(PATH_PARTY.join("pfx")) {
-                    msg("Error", &format!("Couldn't erase pfx data: {}", err));
-                }
-                else if let Err(err) = std::fs::create_dir_all(PATH_PARTY.join("pfx")) {
-                    msg("Error", &format!("Couldn't re-create pfx directory: {}", err));
-                }
-                else {
-                    msg("Data Erased", "Proton prefix data successfully erased.");
-                }
+            let extra_env_label = ui.label("Extra environment variables");
+            let extra_env_editbox = ui.add(
+                egui::TextEdit::multiline(&mut self.options.extra_env_vars)
+                    .desired_rows(2)
+                    .hint_text("VAR=value\nVAR2=value with spaces"),
+            );
+            if extra_env_label.hovered() || extra_env_editbox.hovered() {
+                self.infotext = "Environment variables set for every game, one per line. Values can contain spaces. These are applied after the ones set by the game's handler, so they take priority. If unsure, leave this blank.".to_string();
             }
+        });
+
+        let snapshot_keep_slider = ui.add(
+            egui::Slider::new(&mut self.options.snapshot_keep, 0..=30)
+                .text("Automatic save snapshots to keep"),
+        );
+        if snapshot_keep_slider.hovered() {
+            self.infotext = "Before each launch, PartyDeck backs up every player's save for the game. This sets how many of these backups are kept per profile and game, and likewise for the ones taken before restoring a snapshot or importing a save; snapshots you take yourself are always kept. Set to 0 to turn automatic snapshots off.".to_string();
         }
 
-        if ui.button("Erase Symlink Data").clicked() {
-            if yesno("Erase Symlink Data?", "This will erase all game symlink data. This shouldn't erase profile/game-specific data, but exercise caution. Are you sure?") && PATH_PARTY.join("gamesyms").exists() {
-                if let Err(err) = std::fs::remove_dir_all(PATH_PARTY.join("gamesyms")) {
-                    msg("Error", &format!("Couldn't erase symlink data: {}", err));
-                }
-                else if let Err(err) = std::fs::create_dir_all(PATH_PARTY.join("gamesyms")) {
-                    msg("Error", &format!("Couldn't re-create symlink directory: {}", err));
-                }
-                else {
-                    msg("Data Erased", "Game symlink data successfully erased.");
-                }
-            }
+        let keep_guests_check = ui.checkbox(
+            &mut self.options.keep_guests,
+            "Keep guests until PartyDeck is closed",
+        );
+        if keep_guests_check.hovered() {
+            self.infotext = "Guests normally start fresh every session. With this enabled, guests keep their progress between sessions, and are only removed when PartyDeck is closed. Either way, you'll be asked whether to keep a guest's progress as a new profile before it's removed.".to_string();
         }
-        });
+
+        ui.separator();
 
         ui.horizontal(|ui| {
             if ui.button("Open PartyDeck Data Folder").clicked() {
@@ -427,5 +1296,504 @@ impl PartyApp {
         if kbm_support_check.hovered() {
             self.infotext = "Runs a custom Gamescope build with support for holding keyboards and mice. If you want to use your own Gamescope installation, uncheck this.".to_string();
         }
+
+        ui.horizontal(|ui| {
+            let extra_gsc_label = ui.label("Extra Gamescope arguments");
+            let extra_gsc_editbox = ui.add(
+                egui::TextEdit::singleline(&mut self.options.extra_gamescope_args)
+                    .hint_text("--force-grab-cursor"),
+            );
+            if extra_gsc_label.hovered() || extra_gsc_editbox.hovered() {
+                self.infotext = "Additional arguments passed to every Gamescope instance, separated by spaces. These are added after the ones set by the game's handler. If unsure, leave this blank.".to_string();
+            }
+        });
+    }
+
+    pub fn display_settings_handlers(&mut self, ui: &mut Ui) {
+        ui.label("Trusted handler authors");
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height() - 64.0)
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                let mut remove_key: Option<String> = None;
+                for key in &self.trusted_keys {
+                    ui.horizontal(|ui| {
+                        if ui.button("🗑").clicked() {
+                            remove_key = Some(key.key.clone());
+                        }
+                        ui.label(&key.name);
+                        ui.label(RichText::new(&key.key).small().weak());
+                    });
+                }
+                if let Some(key) = remove_key
+                    && yesno(
+                        "Remove key?",
+                        "Handlers signed with this key will no longer be trusted. Are you sure?",
+                    )
+                {
+                    if let Err(err) = remove_trusted_key(&key) {
+                        msg("Error", &format!("Couldn't remove key: {}", err));
+                    }
+                    self.trusted_keys = load_trusted_keys();
+                }
+            });
+
+        let add_btn = ui.button("Add Trusted Key");
+        if add_btn.hovered() {
+            self.infotext = "Add the public key (minisign/ed25519) of a handler author you trust. Handlers are checked against these keys when installed, using the .minisig signature file placed next to the .pdh file.".to_string();
+        }
+        if add_btn.clicked() {
+            let name = dialog::Input::new("Enter the author's name:")
+                .title("Add Trusted Key")
+                .show()
+                .expect("Could not display dialog box");
+            let key = dialog::Input::new("Paste the author's public key:")
+                .title("Add Trusted Key")
+                .show()
+                .expect("Could not display dialog box");
+            if let (Some(name), Some(key)) = (name, key) {
+                if name.is_empty() {
+                    msg("Error", "Invalid name");
+                } else if let Err(err) = add_trusted_key(&name, &key) {
+                    msg("Error", &format!("Couldn't add key: {}", err));
+                }
+            }
+            self.trusted_keys = load_trusted_keys();
+            self.games = crate::game::scan_all_games();
+        }
+    }
+
+    pub fn display_settings_runtimes(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Available runtimes");
+            if ui.button("🔄").clicked() {
+                self.runtimes = RuntimeRegistry::load();
+            }
+        });
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height() - 64.0)
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                let mut remove_runtime: Option<String> = None;
+                for rt in self.runtimes.runtimes() {
+                    ui.horizontal(|ui| {
+                        if rt.is_custom() && ui.button("🗑").clicked() {
+                            remove_runtime = Some(rt.name().to_string());
+                        }
+                        ui.label(rt.name());
+                        ui.label(RichText::new(rt.description()).small().weak());
+                    });
+                }
+                if let Some(name) = remove_runtime
+                    && yesno(
+                        "Remove runtime?",
+                        &format!("Are you sure you want to remove the custom runtime {name}?"),
+                    )
+                {
+                    if let Err(err) = remove_custom_runtime(&name) {
+                        msg("Error", &format!("Couldn't remove runtime: {}", err));
+                    }
+                    self.runtimes = RuntimeRegistry::load();
+                }
+            });
+
+        let add_btn = ui.button("Add Custom Runtime");
+        if add_btn.hovered() {
+            self.infotext = "Register a command that native Linux games will be launched through, e.g. \"distrobox enter arch --\". Handlers pick a runtime by name with game.runtime.".to_string();
+        }
+        if add_btn.clicked() {
+            let name = dialog::Input::new("Enter a name for the runtime:")
+                .title("Add Custom Runtime")
+                .show()
+                .expect("Could not display dialog box");
+            let command = dialog::Input::new("Enter the command games should be launched with:")
+                .title("Add Custom Runtime")
+                .show()
+                .expect("Could not display dialog box");
+            if let (Some(name), Some(command)) = (name, command)
+                && let Err(err) = add_custom_runtime(&name, &command)
+            {
+                msg("Error", &format!("Couldn't add runtime: {}", err));
+            }
+            self.runtimes = RuntimeRegistry::load();
+        }
+    }
+
+    pub fn display_settings_prefixes(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let mode_label = ui.label("Wine prefix");
+            let r1 = ui.radio_value(&mut self.options.prefix_mode, PrefixMode::Shared, "Shared");
+            let r2 = ui.radio_value(
+                &mut self.options.prefix_mode,
+                PrefixMode::PerGame,
+                "Per game",
+            );
+            let r3 = ui.radio_value(
+                &mut self.options.prefix_mode,
+                PrefixMode::PerProfile,
+                "Per game and profile",
+            );
+            if mode_label.hovered() || r1.hovered() || r2.hovered() || r3.hovered() {
+                self.infotext = "Choose how Windows games are given Wine prefixes. \"Shared\" uses one prefix for every game. \"Per game\" gives each game its own prefix, and \"Per game and profile\" also gives each player their own, which keeps registry-stored saves and settings separate. New prefixes are copied from the template prefix if one is set. If unsure, leave this on \"Shared\".".to_string();
+            }
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("Prefixes");
+            if ui.button("🔄").clicked() {
+                self.prefixes = scan_prefixes();
+            }
+        });
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height() - 64.0)
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                let mut reset: Option<usize> = None;
+                let mut make_template: Option<usize> = None;
+                for (i, pfx) in self.prefixes.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.button("🗑").on_hover_text("Reset").clicked() {
+                            reset = Some(i);
+                        }
+                        if pfx.path != path_prefix_template()
+                            && ui.button("Use as template").clicked()
+                        {
+                            make_template = Some(i);
+                        }
+                        ui.label(&pfx.label);
+                        ui.label(RichText::new(human_size(pfx.size)).small().weak());
+                        if !pfx.verbs.is_empty() {
+                            ui.label(RichText::new(pfx.verbs.join(", ")).small().weak());
+                        }
+                    });
+                }
+                if let Some(i) = reset {
+                    let pfx = &self.prefixes[i];
+                    if yesno(
+                        "Reset Prefix?",
+                        &format!("This will erase the Wine prefix \"{}\". Anything the game stored in the prefix, such as registry settings, will be lost. Are you sure?", pfx.label),
+                    ) {
+                        if let Err(err) = reset_prefix(&pfx.path) {
+                            msg("Error", &format!("Couldn't reset prefix: {}", err));
+                        }
+                        self.prefixes = scan_prefixes();
+                    }
+                }
+                if let Some(i) = make_template {
+                    let pfx = &self.prefixes[i];
+                    if yesno(
+                        "Use as Template?",
+                        &format!("New prefixes will be created as copies of \"{}\". Are you sure?", pfx.label),
+                    ) {
+                        if let Err(err) = set_prefix_template(&pfx.path) {
+                            msg("Error", &format!("Couldn't copy prefix: {}", err));
+                        }
+                        self.prefixes = scan_prefixes();
+                    }
+                }
+            });
+    }
+
+    fn handler_uids(&self) -> Vec<String> {
+        self.games
+            .iter()
+            .filter_map(|g| match g {
+                HandlerRef(h) => Some(h.uid.clone()),
+                ExecRef(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn display_settings_storage(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let total: u64 = self
+                .storage
+                .iter()
+                // Saves and per-profile prefixes are already counted in their profile
+                .filter(|item| {
+                    item.kind == StorageKind::Profile
+                        || !item.path.starts_with(PATH_PARTY.join("profiles"))
+                })
+                .map(|item| item.size)
+                .sum();
+            ui.label(format!("Total: {}", human_size(total)));
+            if ui.button("🔄").clicked() {
+                self.storage = scan_storage(&self.handler_uids());
+            }
+            let orphans = self.storage.iter().filter(|item| item.orphan).count();
+            let clean_btn = ui.add_enabled(orphans > 0, egui::Button::new("Remove Orphaned Data"));
+            if clean_btn.hovered() {
+                self.infotext = "Removes symlink folders, overlays, prefixes, saves, snapshots and settings belonging to games that have been removed from PartyDeck.".to_string();
+            }
+            if clean_btn.clicked() {
+                let list: String = self
+                    .storage
+                    .iter()
+                    .filter(|item| item.orphan)
+                    .map(|item| format!("\n• {} ({}, {})", item.label, kind_name(item.kind), human_size(item.size)))
+                    .collect();
+                if !yesno(
+                    "Remove Orphaned Data?",
+                    &format!("This will erase {orphans} items left behind by removed games, including their save data:\n{list}\n\nAre you sure?"),
+                ) {
+                    return;
+                }
+                for item in self.storage.iter().filter(|item| item.orphan) {
+                    if let Err(err) = remove_storage_item(item) {
+                        msg("Error", &format!("Couldn't remove {}: {}", item.label, err));
+                    }
+                }
+                self.storage = scan_storage(&self.handler_uids());
+            }
+        });
+
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height() - 64.0)
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                let mut remove: Option<usize> = None;
+                let kinds = [
+                    StorageKind::SymlinkFolder,
+                    StorageKind::Overlay,
+                    StorageKind::Prefix,
+                    StorageKind::Profile,
+                    StorageKind::Save,
+                    StorageKind::Snapshot,
+                    StorageKind::Overrides,
+                ];
+                for kind in kinds {
+                    if !self.storage.iter().any(|item| item.kind == kind) {
+                        continue;
+                    }
+                    ui.label(RichText::new(kind.title()).strong());
+                    for (i, item) in self.storage.iter().enumerate() {
+                        if item.kind != kind {
+                            continue;
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.button("🗑").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.label(&item.label);
+                            ui.label(RichText::new(human_size(item.size)).small().weak());
+                            if item.orphan {
+                                ui.label(
+                                    RichText::new("orphaned")
+                                        .small()
+                                        .color(egui::Color32::LIGHT_RED),
+                                );
+                            }
+                        });
+                    }
+                    ui.add_space(8.0);
+                }
+
+                if let Some(i) = remove {
+                    let item = &self.storage[i];
+                    let warning = match item.kind {
+                        StorageKind::SymlinkFolder | StorageKind::Overlay => "It will be rebuilt the next time the game is launched.",
+                        StorageKind::Prefix => "Anything the game stored in the prefix, such as registry settings, will be lost.",
+                        StorageKind::Profile => "All of this profile's save data, settings and stats will be lost.",
+                        StorageKind::Save => "This profile's save data and settings for the game will be lost.",
+                        StorageKind::Snapshot => "All of this profile's backups of the game's save will be lost.",
+                        StorageKind::Overrides => "The game will go back to using the global settings.",
+                    };
+                    if yesno(
+                        "Erase Data?",
+                        &format!("This will erase \"{}\" ({}). {warning} Are you sure?", item.label, kind_name(item.kind)),
+                    ) {
+                        if let Err(err) = remove_storage_item(item) {
+                            msg("Error", &format!("Couldn't erase data: {}", err));
+                        }
+                        self.storage = scan_storage(&self.handler_uids());
+                        self.profiles = scan_profiles(false);
+                        self.profile_edit = None;
+                        self.profile_stats.clear();
+                        self.game_overrides = None;
+                    }
+                }
+            });
+    }
+
+    pub fn display_settings_dependencies(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Tools PartyDeck needs to run games");
+            if ui.button("🔄").clicked() {
+                self.deps = DEPENDENCIES.iter().map(dep_status).collect();
+            }
+        });
+        ui.label(
+            RichText::new(
+                "Changes to installed dependencies take effect after restarting PartyDeck.",
+            )
+            .small()
+            .weak(),
+        );
+        ui.separator();
+
+        let mut install: Option<(&'static Dependency, String)> = None;
+        let mut rollback: Option<&'static Dependency> = None;
+        for (dep, status) in DEPENDENCIES.iter().zip(self.deps.iter()) {
+            ui.label(RichText::new(dep.display).strong());
+            let can_rollback = match status {
+                DepStatus::Managed(installed, valid) => {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Version {}", installed.version));
+                        match valid {
+                            true => ui.label(RichText::new("checksum ok").small().weak()),
+                            false => ui.label(
+                                RichText::new("checksum mismatch, reinstall recommended")
+                                    .small()
+                                    .color(egui::Color32::LIGHT_RED),
+                            ),
+                        };
+                    });
+                    installed.previous.is_some()
+                }
+                DepStatus::External(path) => {
+                    ui.label(format!("Found at {}", path.display()));
+                    false
+                }
+                DepStatus::Missing => {
+                    ui.label(RichText::new("Not installed").color(egui::Color32::LIGHT_RED));
+                    false
+                }
+            };
+
+            ui.horizontal(|ui| {
+                let source = self
+                    .options
+                    .dep_sources
+                    .entry(dep.name.to_string())
+                    .or_default();
+                let source_label = ui.label("Source");
+                let source_edit =
+                    ui.add(egui::TextEdit::singleline(source).hint_text(dep.default_source));
+                if source_label.hovered() || source_edit.hovered() {
+                    self.infotext = "Where to install from: \"github:owner/repo\" for the latest release, a URL to an archive, or the path to a local archive. Leave empty to use the default.".to_string();
+                }
+
+                let install_label = match status {
+                    DepStatus::Managed(..) => "Update",
+                    _ => "Install",
+                };
+                if ui.button(install_label).clicked() {
+                    install = Some((dep, source.clone()));
+                }
+                if ui
+                    .add_enabled(can_rollback, egui::Button::new("Rollback"))
+                    .clicked()
+                {
+                    rollback = Some(dep);
+                }
+            });
+            ui.add_space(8.0);
+        }
+
+        if let Some((dep, source)) = install {
+            let _ = save_cfg(&self.options);
+            self.spawn_task(&format!("Installing {}...", dep.display), move || {
+                if let Err(err) = install_dependency(dep, &source) {
+                    println!("{}", err);
+                    msg(
+                        "Error",
+                        &format!("Couldn't install {}: {}", dep.display, err),
+                    );
+                } else {
+                    msg(
+                        "Installed",
+                        &format!(
+                            "{} was installed. Restart PartyDeck to use it.",
+                            dep.display
+                        ),
+                    );
+                }
+            });
+        }
+        if let Some(dep) = rollback {
+            if let Err(err) = rollback_dependency(dep) {
+                msg(
+                    "Error",
+                    &format!("Couldn't roll back {}: {}", dep.display, err),
+                );
+            }
+            self.deps = DEPENDENCIES.iter().map(dep_status).collect();
+        }
+    }
+
+    pub fn display_settings_health_check(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let errors = self
+                .checks
+                .iter()
+                .filter(|c| c.status == CheckStatus::Error)
+                .count();
+            match errors {
+                0 => ui.label("Everything needed to launch games is set up"),
+                _ => ui.label(format!("{errors} problems found")),
+            };
+            if ui.button("🔄").clicked() {
+                self.checks = run_checks(&self.options);
+            }
+        });
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height() - 64.0)
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                for c in &self.checks {
+                    let color = match c.status {
+                        CheckStatus::Ok => egui::Color32::LIGHT_GREEN,
+                        CheckStatus::Warning => egui::Color32::YELLOW,
+                        CheckStatus::Error => egui::Color32::LIGHT_RED,
+                    };
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(c.status.label()).monospace().color(color));
+                        ui.label(RichText::new(c.name).strong());
+                        ui.label(RichText::new(&c.detail).small().weak());
+                    });
+                    if c.status != CheckStatus::Ok && !c.fix.is_empty() {
+                        ui.label(RichText::new(&c.fix).small());
+                    }
+                }
+            });
+    }
+}
+
+fn time_ago(secs: u64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let elapsed = now.saturating_sub(secs);
+    match elapsed {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{} minutes ago", elapsed / 60),
+        3600..86400 => format!("{} hours ago", elapsed / 3600),
+        86400..172800 => "yesterday".to_string(),
+        _ => format!("{} days ago", elapsed / 86400),
+    }
+}
+
+enum SnapshotAction {
+    Compare,
+    Restore,
+    Delete,
+}
+
+fn kind_name(kind: StorageKind) -> &'static str {
+    match kind {
+        StorageKind::SymlinkFolder => "game symlink folder",
+        StorageKind::Overlay => "game overlay",
+        StorageKind::Prefix => "Wine prefix",
+        StorageKind::Profile => "profile",
+        StorageKind::Save => "game save",
+        StorageKind::Snapshot => "save snapshots",
+        StorageKind::Overrides => "game settings",
     }
 }
@@ -196,6 +196,8 @@ impl PartyApp {
         // Hacky workaround to avoid borrowing conflicts from inside the loop
         if refresh_games {
             self.games = scan_all_games();
+            self.game_overrides = None;
+            self.game_interfaces = None;
         }
     }
 }
@@ -3,8 +3,11 @@ mod app_light;
 mod config;
 mod gui_pages;
 mod gui_panels;
+mod widgets;
 
 pub use app::PartyApp;
 pub use app_light::LightPartyApp;
 pub use config::PadFilterType;
 pub use config::PartyConfig;
+pub use config::PrefixMode;
+pub use config::load_cfg;
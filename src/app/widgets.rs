@@ -0,0 +1,194 @@
+use super::config::{GameOverrides, PartyConfig};
+use crate::proton::*;
+
+use eframe::egui::{self, RichText, Ui};
+use rfd::FileDialog;
+
+// Dropdown for picking a Proton build. An empty value means "use the default",
+// which is described by default_label (GE-Proton globally, the global setting per game).
+pub fn proton_selector(
+    ui: &mut Ui,
+    id_salt: &str,
+    value: &mut String,
+    builds: &[ProtonBuild],
+    default_label: &str,
+) -> egui::Response {
+    let selected_text = if value.is_empty() {
+        default_label.to_string()
+    } else if let Some(build) = builds.iter().find(|b| &b.name == value) {
+        build.display()
+    } else if is_umu_keyword(value) {
+        format!("{value} (UMU)")
+    } else {
+        format!("Custom: {value}")
+    };
+
+    let combo = egui::ComboBox::from_id_salt(id_salt)
+        .selected_text(selected_text)
+        .width(260.0)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(value, String::new(), default_label);
+            ui.selectable_value(
+                value,
+                "GE-Proton".to_string(),
+                "GE-Proton (latest, downloaded by UMU)",
+            );
+            for build in builds {
+                ui.selectable_value(value, build.name.clone(), build.display());
+            }
+            if ui.button("Browse...").clicked()
+                && let Some(path) = FileDialog::new()
+                    .set_title("Select Proton folder")
+                    .pick_folder()
+            {
+                *value = path.to_string_lossy().to_string();
+            }
+        });
+    combo.response
+}
+
+// A setting that can be overridden: ticking the box starts the override from the value
+// underneath, which is shown next to it. Returns whether the row is hovered.
+fn override_row<T: Clone>(
+    ui: &mut Ui,
+    label: &str,
+    value: &mut Option<T>,
+    base: &T,
+    base_name: &str,
+    describe: impl Fn(&T) -> String,
+    edit: impl FnOnce(&mut Ui, &mut T) -> egui::Response,
+) -> bool {
+    ui.horizontal(|ui| {
+        let mut overridden = value.is_some();
+        let check = ui.checkbox(&mut overridden, label);
+        if overridden != value.is_some() {
+            *value = overridden.then(|| base.clone());
+        }
+        let edited = match value {
+            Some(value) => {
+                let edited = edit(ui, value);
+                ui.label(
+                    RichText::new(format!("{base_name}: {}", describe(base)))
+                        .small()
+                        .weak(),
+                );
+                edited
+            }
+            None => ui.label(RichText::new(describe(base)).weak()),
+        };
+        check.hovered() || edited.hovered()
+    })
+    .inner
+}
+
+// Settings a game (or a single launch) can change from the ones underneath, named by base_name.
+// Returns help text for the hovered row.
+pub fn overrides_editor(
+    ui: &mut Ui,
+    id_salt: &str,
+    overrides: &mut GameOverrides,
+    base: &PartyConfig,
+    base_name: &str,
+    builds: &[ProtonBuild],
+    win: bool,
+) -> Option<&'static str> {
+    let on_off = |value: &bool| match value {
+        true => "On".to_string(),
+        false => "Off".to_string(),
+    };
+    let toggle = |ui: &mut Ui, value: &mut bool| {
+        ui.radio_value(value, true, "On") | ui.radio_value(value, false, "Off")
+    };
+    let mut hovered = None;
+
+    if override_row(
+        ui,
+        "Instance resolution scale",
+        &mut overrides.render_scale,
+        &base.render_scale,
+        base_name,
+        |value| format!("{value}%"),
+        |ui, value| ui.add(egui::Slider::new(value, 35..=200).suffix("%")),
+    ) {
+        hovered = Some(
+            "Some games need a higher resolution scale to be readable, or a lower one to run well. Tick to use a different scale here.",
+        );
+    }
+    if override_row(
+        ui,
+        "Gamescope SDL backend",
+        &mut overrides.gamescope_sdl_backend,
+        &base.gamescope_sdl_backend,
+        base_name,
+        on_off,
+        toggle,
+    ) {
+        hovered = Some(
+            "Some games only show a black screen with Gamescope's SDL backend. Tick to turn it on or off here.",
+        );
+    }
+    if override_row(
+        ui,
+        "Keyboard and mouse support",
+        &mut overrides.kbm_support,
+        &base.kbm_support,
+        base_name,
+        on_off,
+        toggle,
+    ) {
+        hovered = Some(
+            "Tick to choose here whether the custom Gamescope with keyboard and mouse support is used.",
+        );
+    }
+    if !win
+        && override_row(
+            ui,
+            "Force Steam Runtime SDL2",
+            &mut overrides.force_sdl,
+            &base.force_sdl,
+            base_name,
+            on_off,
+            toggle,
+        )
+    {
+        hovered = Some(
+            "Tick to choose here whether the Steam Runtime's SDL2 is used. It fixes controller mappings in some native games and breaks others.",
+        );
+    }
+    if override_row(
+        ui,
+        "Vertical split for 2 players",
+        &mut overrides.vertical_two_player,
+        &base.vertical_two_player,
+        base_name,
+        on_off,
+        toggle,
+    ) {
+        hovered = Some("Tick to choose here whether two players are split side by side.");
+    }
+    if win
+        && override_row(
+            ui,
+            "Proton version",
+            &mut overrides.proton_version,
+            &base.proton_version,
+            base_name,
+            |value| match value.is_empty() {
+                true => "GE-Proton (default)".to_string(),
+                false => value.clone(),
+            },
+            |ui, value| {
+                proton_selector(
+                    ui,
+                    &format!("{id_salt}_proton"),
+                    value,
+                    builds,
+                    "GE-Proton (default)",
+                )
+            },
+        )
+    {
+        hovered = Some("Tick to pick a different Proton build here.");
+    }
+    hovered
+}
@@ -0,0 +1,378 @@
+use crate::paths::*;
+use crate::util::{copy_dir_recursive, set_progress, sha256_file};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+pub struct Dependency {
+    pub name: &'static str,
+    pub display: &'static str,
+    // "github:owner/repo" for the latest release, a URL to an archive, or a local archive
+    pub default_source: &'static str,
+    // Release assets to download, matched by the end of their names
+    assets: &'static [&'static str],
+    // Must be present in every install, and is what the checksum is taken of
+    key_file: &'static str,
+}
+
+pub static DEPENDENCIES: [Dependency; 2] = [
+    Dependency {
+        name: "goldberg",
+        display: "Goldberg Steam Emu (gbe_fork)",
+        default_source: "github:Detanup01/gbe_fork",
+        assets: &["emu-linux-release.tar.bz2", "emu-win-release.7z"],
+        key_file: "generate_interfaces_x64",
+    },
+    Dependency {
+        name: "umu",
+        display: "UMU Launcher",
+        default_source: "github:Open-Wine-Components/umu-launcher",
+        assets: &["zipapp.tar"],
+        key_file: "umu-run",
+    },
+];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InstalledDep {
+    pub version: String,
+    pub source: String,
+    pub sha256: String,
+    // The version that was active before this one, kept around for rolling back
+    #[serde(default)]
+    pub previous: Option<Box<InstalledDep>>,
+}
+
+pub enum DepStatus {
+    // Installed by us; false if the key file no longer matches its checksum
+    Managed(InstalledDep, bool),
+    // Found outside of the dependency manager, e.g. installed system-wide or bundled
+    External(PathBuf),
+    Missing,
+}
+
+fn load_manifest() -> HashMap<String, InstalledDep> {
+    if let Ok(file) = File::open(PATH_DEPS.join("deps.json"))
+        && let Ok(manifest) = serde_json::from_reader(BufReader::new(file))
+    {
+        return manifest;
+    }
+    HashMap::new()
+}
+
+fn save_manifest(manifest: &HashMap<String, InstalledDep>) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(&*PATH_DEPS)?;
+    let file = File::create(PATH_DEPS.join("deps.json"))?;
+    serde_json::to_writer_pretty(file, manifest)?;
+    Ok(())
+}
+
+// Where the rest of PartyDeck will look for the dependency's key file
+fn resolved_key_file(dep: &Dependency) -> PathBuf {
+    match dep.name {
+        "umu" => BIN_UMU_RUN.clone(),
+        _ => PATH_GOLDBERG.join(dep.key_file),
+    }
+}
+
+pub fn dep_status(dep: &Dependency) -> DepStatus {
+    let key_file = resolved_key_file(dep);
+    if !key_file.exists() {
+        return DepStatus::Missing;
+    }
+    if !key_file.starts_with(&*PATH_DEPS) {
+        return DepStatus::External(key_file);
+    }
+    match load_manifest().remove(dep.name) {
+        Some(installed) => {
+            let valid = sha256_file(&key_file).is_ok_and(|sha| sha == installed.sha256);
+            DepStatus::Managed(installed, valid)
+        }
+        None => DepStatus::External(key_file),
+    }
+}
+
+fn download(url: &str, dest: &PathBuf) -> Result<(), Box<dyn Error>> {
+    println!("Downloading {url}");
+    let mut response = reqwest::blocking::Client::new()
+        .get(url)
+        .header("User-Agent", "partydeck")
+        .send()?
+        .error_for_status()?;
+    let mut file = File::create(dest)?;
+    response.copy_to(&mut file)?;
+    Ok(())
+}
+
+// Gets the archives for a dependency into dir, returning the version they're for
+fn fetch(
+    dep: &Dependency,
+    source: &str,
+    dir: &Path,
+) -> Result<(String, Vec<PathBuf>), Box<dyn Error>> {
+    if let Some(repo) = source.strip_prefix("github:") {
+        let release: Value = reqwest::blocking::Client::new()
+            .get(format!(
+                "https://api.github.com/repos/{repo}/releases/latest"
+            ))
+            .header("User-Agent", "partydeck")
+            .send()?
+            .error_for_status()?
+            .json()?;
+        let version = release["tag_name"]
+            .as_str()
+            .ok_or("Release has no tag")?
+            .to_string();
+
+        let mut archives = Vec::new();
+        for asset in release["assets"]
+            .as_array()
+            .ok_or("Release has no assets")?
+        {
+            let name = asset["name"].as_str().unwrap_or_default();
+            if name.contains('/') || !dep.assets.iter().any(|a| name.ends_with(a)) {
+                continue;
+            }
+            let url = asset["browser_download_url"]
+                .as_str()
+                .ok_or("Asset has no download URL")?;
+            let path = dir.join(name);
+            set_progress(&format!("Downloading {name}..."));
+            download(url, &path)?;
+
+            // GitHub publishes a digest for each asset, check it when it's there
+            if let Some(expected) = asset["digest"]
+                .as_str()
+                .and_then(|d| d.strip_prefix("sha256:"))
+                && sha256_file(&path)? != expected
+            {
+                return Err(format!("Checksum mismatch for {name}").into());
+            }
+            archives.push(path);
+        }
+        if archives.is_empty() {
+            return Err(format!("No {} downloads found in release {version}", dep.display).into());
+        }
+        return Ok((version, archives));
+    }
+
+    let name = source
+        .rsplit('/')
+        .next()
+        .filter(|n| !n.is_empty())
+        .ok_or("Invalid source")?
+        .to_string();
+    let version = name.split(".tar").next().unwrap_or(&name).to_string();
+    let path = dir.join(&name);
+    if source.starts_with("http://") || source.starts_with("https://") {
+        set_progress(&format!("Downloading {name}..."));
+        download(source, &path)?;
+    } else {
+        std::fs::copy(source, &path)?;
+    }
+    Ok((version, vec![path]))
+}
+
+// Arranges gbe_fork's release folders into the layout handlers expect:
+// {linux,win}/{x32,x64} for the emulator, and the interface generators at the top. Each
+// archive's folder is filed on its own, going by the steam_api it has.
+fn layout_goldberg(extracted: &Path, dest: &Path) -> Result<(), Box<dyn Error>> {
+    for tree in std::fs::read_dir(extracted)?.flatten() {
+        layout_goldberg_tree(&tree.path(), dest)?;
+    }
+    Ok(())
+}
+
+fn layout_goldberg_tree(extracted: &Path, dest: &Path) -> Result<(), Box<dyn Error>> {
+    for entry in walkdir::WalkDir::new(extracted).into_iter().flatten() {
+        let path = entry.path().to_path_buf();
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if entry.file_type().is_file() && name.starts_with("generate_interfaces_x") {
+            if !name.contains('.') {
+                std::fs::copy(&path, dest.join(&name))?;
+            }
+            continue;
+        }
+        if !entry.file_type().is_dir() || !["x32", "x64"].contains(&name.as_str()) {
+            continue;
+        }
+        if !path
+            .parent()
+            .is_some_and(|p| p.ends_with("regular") || p.ends_with("release"))
+        {
+            continue;
+        }
+        let platform = if path.join("libsteam_api.so").exists() {
+            "linux"
+        } else if path.join("steam_api.dll").exists() || path.join("steam_api64.dll").exists() {
+            "win"
+        } else {
+            continue;
+        };
+        copy_dir_recursive(&path, &dest.join(platform).join(&name), false, true)?;
+    }
+    Ok(())
+}
+
+fn layout_umu(extracted: &PathBuf, dest: &PathBuf) -> Result<(), Box<dyn Error>> {
+    let umu_run = walkdir::WalkDir::new(extracted)
+        .into_iter()
+        .flatten()
+        .find(|e| e.file_type().is_file() && e.file_name() == "umu-run")
+        .ok_or("umu-run not found in archive")?;
+    let dir = umu_run
+        .path()
+        .parent()
+        .ok_or("Invalid archive")?
+        .to_path_buf();
+    copy_dir_recursive(&dir, dest, false, true)?;
+    Ok(())
+}
+
+fn set_current(dep: &Dependency, version: &str) -> Result<(), Box<dyn Error>> {
+    let current = PATH_DEPS.join(dep.name).join("current");
+    if current.symlink_metadata().is_ok() {
+        std::fs::remove_file(&current)?;
+    }
+    std::os::unix::fs::symlink(version, &current)?;
+    Ok(())
+}
+
+// Keeps the active and previous versions, removing any others
+fn remove_old_versions(dep: &Dependency, installed: &InstalledDep) -> Result<(), Box<dyn Error>> {
+    let keep: Vec<&str> = [Some(installed), installed.previous.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(|i| i.version.as_str())
+        .chain(["current"])
+        .collect();
+    for entry in std::fs::read_dir(PATH_DEPS.join(dep.name))?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !keep.contains(&name.as_str()) && entry.file_type()?.is_dir() {
+            std::fs::remove_dir_all(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+pub fn install_dependency(dep: &Dependency, source: &str) -> Result<(), Box<dyn Error>> {
+    let source = match source.trim().is_empty() {
+        true => dep.default_source,
+        false => source.trim(),
+    };
+    let path_dep = PATH_DEPS.join(dep.name);
+    let staging = path_dep.join(".staging");
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::create_dir_all(staging.join("extracted"))?;
+
+    let (version, archives) = fetch(dep, source, &staging)?;
+    let version = version.replace(['/', '\\'], "_");
+
+    // gbe_fork's Linux and Windows archives have the same layout, so each gets its own folder
+    for (i, archive) in archives.iter().enumerate() {
+        set_progress(&format!("Extracting {}...", archive.display()));
+        let extracted = staging.join("extracted").join(i.to_string());
+        std::fs::create_dir_all(&extracted)?;
+        compress_tools::uncompress_archive(
+            File::open(archive)?,
+            &extracted,
+            compress_tools::Ownership::Ignore,
+        )?;
+    }
+
+    let dest = path_dep.join(&version);
+    if dest.exists() {
+        std::fs::remove_dir_all(&dest)?;
+    }
+    std::fs::create_dir_all(&dest)?;
+    match dep.name {
+        "goldberg" => layout_goldberg(&staging.join("extracted"), &dest)?,
+        _ => layout_umu(&staging.join("extracted"), &dest)?,
+    }
+    std::fs::remove_dir_all(&staging)?;
+
+    let key_file = dest.join(dep.key_file);
+    if !key_file.exists() {
+        return Err(format!("{} is missing from {} {version}", dep.key_file, dep.display).into());
+    }
+    std::fs::set_permissions(&key_file, std::fs::Permissions::from_mode(0o755))?;
+
+    let mut manifest = load_manifest();
+    let previous = manifest
+        .remove(dep.name)
+        .filter(|old| old.version != version)
+        .map(|mut old| {
+            old.previous = None;
+            Box::new(old)
+        });
+    let installed = InstalledDep {
+        version: version.clone(),
+        source: source.to_string(),
+        sha256: sha256_file(&key_file)?,
+        previous,
+    };
+
+    set_current(dep, &version)?;
+    remove_old_versions(dep, &installed)?;
+    manifest.insert(dep.name.to_string(), installed);
+    save_manifest(&manifest)?;
+
+    println!("Installed {} {version}", dep.display);
+    Ok(())
+}
+
+pub fn rollback_dependency(dep: &Dependency) -> Result<(), Box<dyn Error>> {
+    let mut manifest = load_manifest();
+    let mut current = manifest
+        .remove(dep.name)
+        .ok_or("Dependency isn't managed by PartyDeck")?;
+    let mut previous = current
+        .previous
+        .take()
+        .ok_or("No previous version to go back to")?;
+    if !PATH_DEPS.join(dep.name).join(&previous.version).exists() {
+        return Err(format!("Version {} is no longer installed", previous.version).into());
+    }
+
+    set_current(dep, &previous.version)?;
+    previous.previous = Some(Box::new(current));
+    manifest.insert(dep.name.to_string(), *previous);
+    save_manifest(&manifest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_goldberg_keeps_platforms_apart() {
+        let dir = std::env::temp_dir().join(format!("partydeck-deps-{}", std::process::id()));
+        let extracted = dir.join("extracted");
+        let dest = dir.join("dest");
+        for (tree, lib) in [("0", "libsteam_api.so"), ("1", "steam_api64.dll")] {
+            for arch in ["x32", "x64"] {
+                let path = extracted.join(tree).join("release/regular").join(arch);
+                std::fs::create_dir_all(&path).unwrap();
+                std::fs::write(path.join(lib), "").unwrap();
+            }
+        }
+        std::fs::create_dir_all(&dest).unwrap();
+
+        layout_goldberg(&extracted, &dest).unwrap();
+        let linux = dest.join("linux/x64/libsteam_api.so").exists();
+        let win = dest.join("win/x64/steam_api64.dll").exists();
+        let mixed = dest.join("linux/x64/steam_api64.dll").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(linux && win && !mixed);
+    }
+}
@@ -0,0 +1,367 @@
+use crate::app::PartyConfig;
+use crate::deps::*;
+use crate::overlay::{OverlayBackend, overlay_backend};
+use crate::paths::*;
+use crate::proton::{is_umu_keyword, resolve_proton};
+use crate::runtime::RuntimeRegistry;
+use crate::util::kwin_dbus_available;
+
+use std::path::PathBuf;
+use std::process::Command;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl CheckStatus {
+    pub fn label(&self) -> &str {
+        match self {
+            CheckStatus::Ok => "OK",
+            CheckStatus::Warning => "WARN",
+            CheckStatus::Error => "FAIL",
+        }
+    }
+}
+
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    // What the user can do about it; empty when the check passed
+    pub fix: String,
+}
+
+fn check(name: &'static str, status: CheckStatus, detail: &str, fix: &str) -> Check {
+    Check {
+        name,
+        status,
+        detail: detail.to_string(),
+        fix: fix.to_string(),
+    }
+}
+
+// First line a program prints for its version, which some print to stderr
+fn program_version(program: &str) -> Option<String> {
+    let output = Command::new(program).arg("--version").output().ok()?;
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Some(
+        text.lines()
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    )
+}
+
+fn check_gamescope(cfg: &PartyConfig) -> Vec<Check> {
+    let mut out = Vec::new();
+    out.push(match program_version("gamescope") {
+        Some(version) => check("Gamescope", CheckStatus::Ok, &version, ""),
+        None => check(
+            "Gamescope",
+            CheckStatus::Error,
+            "gamescope was not found",
+            "Install gamescope from your distribution's packages.",
+        ),
+    });
+
+    let status = match cfg.kbm_support {
+        true => CheckStatus::Error,
+        false => CheckStatus::Warning,
+    };
+    out.push(match program_version(&BIN_GSC_KBM.to_string_lossy()) {
+        Some(version) => check("Gamescope (mouse/keyboard)", CheckStatus::Ok, &version, ""),
+        None => check(
+            "Gamescope (mouse/keyboard)",
+            status,
+            &format!("{} was not found", BIN_GSC_KBM.display()),
+            "Reinstall PartyDeck, or build gamescope-kbm as described in the README. Turning off mouse/keyboard support in Settings also avoids needing it.",
+        ),
+    });
+    out
+}
+
+fn check_bwrap() -> Vec<Check> {
+    let mut out = Vec::new();
+    let Some(version) = program_version("bwrap") else {
+        out.push(check(
+            "Bubblewrap",
+            CheckStatus::Error,
+            "bwrap was not found",
+            "Install bubblewrap from your distribution's packages.",
+        ));
+        return out;
+    };
+    out.push(check("Bubblewrap", CheckStatus::Ok, &version, ""));
+
+    let sandboxed = Command::new("bwrap")
+        .args(["--ro-bind", "/", "/", "--dev", "/dev", "true"])
+        .output()
+        .is_ok_and(|o| o.status.success());
+    out.push(match sandboxed {
+        true => check(
+            "User namespaces",
+            CheckStatus::Ok,
+            "bwrap can create sandboxes",
+            "",
+        ),
+        false => check(
+            "User namespaces",
+            CheckStatus::Error,
+            "bwrap couldn't create a sandbox",
+            "Allow unprivileged user namespaces: run \"sudo sysctl kernel.unprivileged_userns_clone=1\", and on Ubuntu also \"sudo sysctl kernel.apparmor_restrict_unprivileged_userns=0\".",
+        ),
+    });
+
+    out.push(match overlay_backend() {
+        Some(OverlayBackend::Bwrap) => check(
+            "Game folder overlays",
+            CheckStatus::Ok,
+            "Using bwrap overlays",
+            "",
+        ),
+        Some(OverlayBackend::Fuse) => check(
+            "Game folder overlays",
+            CheckStatus::Ok,
+            "Using fuse-overlayfs",
+            "",
+        ),
+        None => check(
+            "Game folder overlays",
+            CheckStatus::Warning,
+            "Not supported, game folders will be symlinked instead",
+            "Update bubblewrap to 0.10 or newer, or install fuse-overlayfs.",
+        ),
+    });
+    out
+}
+
+fn check_kwin(cfg: &PartyConfig) -> Check {
+    if !cfg.enable_kwin_script {
+        return check(
+            "KWin scripting",
+            CheckStatus::Ok,
+            "Not used, the KWin script is turned off in Settings",
+            "",
+        );
+    }
+    match kwin_dbus_available() {
+        Ok(()) => check(
+            "KWin scripting",
+            CheckStatus::Ok,
+            "Reachable over D-Bus",
+            "",
+        ),
+        Err(err) => check(
+            "KWin scripting",
+            CheckStatus::Error,
+            &format!("Couldn't reach KWin over D-Bus: {err}"),
+            "Run PartyDeck in a KDE Plasma session, or start it with --kwin. On other desktops, turn off the KWin script in Settings and arrange the windows yourself.",
+        ),
+    }
+}
+
+fn check_steam(cfg: &PartyConfig) -> Vec<Check> {
+    let mut out = Vec::new();
+    // PATH_STEAM asks the user for the folder if Steam can't be found, so don't touch it
+    // (or anything that uses it) unless Steam is actually there
+    let Some(steam) = locate_steam() else {
+        out.push(check(
+            "Steam",
+            CheckStatus::Warning,
+            "Steam was not found; runtimes and Proton builds installed through Steam were not checked",
+            "Install Steam, or set STEAM_BASE_FOLDER to where it is installed. PartyDeck will ask for its folder when it's needed.",
+        ));
+        return out;
+    };
+    out.push(check(
+        "Steam",
+        CheckStatus::Ok,
+        &steam.display().to_string(),
+        "",
+    ));
+
+    let runtimes = RuntimeRegistry::load();
+    let names = runtimes
+        .runtimes()
+        .iter()
+        .map(|r| r.name())
+        .collect::<Vec<&str>>()
+        .join(", ");
+    out.push(match runtimes.runtimes().len() > 1 {
+        true => check("Runtimes", CheckStatus::Ok, &names, ""),
+        false => check(
+            "Runtimes",
+            CheckStatus::Warning,
+            "Only the host runtime is available",
+            "Install \"Steam Linux Runtime\" from your Steam library for handlers that ask for one.",
+        ),
+    });
+
+    out.push(match resolve_proton(&cfg.proton_version) {
+        Ok(proton) if is_umu_keyword(&proton) => check(
+            "Proton",
+            CheckStatus::Ok,
+            &format!("{proton}, downloaded by UMU when needed"),
+            "",
+        ),
+        Ok(proton) => check("Proton", CheckStatus::Ok, &proton, ""),
+        Err(err) => check(
+            "Proton",
+            CheckStatus::Error,
+            &err.to_string(),
+            "Pick an installed Proton build in Settings.",
+        ),
+    });
+    out
+}
+
+fn check_dependencies() -> Vec<Check> {
+    let mut out = Vec::new();
+    for dep in DEPENDENCIES.iter() {
+        let name = dep.display;
+        out.push(match dep_status(dep) {
+            DepStatus::Managed(installed, true) => check(
+                name,
+                CheckStatus::Ok,
+                &format!("Version {}", installed.version),
+                "",
+            ),
+            DepStatus::Managed(installed, false) => check(
+                name,
+                CheckStatus::Warning,
+                &format!(
+                    "Version {}, but its files have changed since it was installed",
+                    installed.version
+                ),
+                "Reinstall it from Settings > Dependencies.",
+            ),
+            DepStatus::External(path) => {
+                check(name, CheckStatus::Ok, &path.display().to_string(), "")
+            }
+            DepStatus::Missing => check(
+                name,
+                CheckStatus::Error,
+                "Not installed",
+                "Install it from Settings > Dependencies.",
+            ),
+        });
+    }
+    out
+}
+
+fn check_input() -> Check {
+    let mut total = 0;
+    let mut readable = 0;
+    if let Ok(entries) = std::fs::read_dir("/dev/input") {
+        for entry in entries.flatten() {
+            if !entry.file_name().to_string_lossy().starts_with("event") {
+                continue;
+            }
+            total += 1;
+            if std::fs::File::open(entry.path()).is_ok() {
+                readable += 1;
+            }
+        }
+    }
+
+    let fix = "Add yourself to the input group with \"sudo usermod -aG input $USER\" and log back in, or install Steam's udev rules (steam-devices).";
+    if total == 0 {
+        check(
+            "Input devices",
+            CheckStatus::Error,
+            "No devices found in /dev/input",
+            fix,
+        )
+    } else if readable == 0 {
+        check(
+            "Input devices",
+            CheckStatus::Error,
+            &format!("None of the {total} devices in /dev/input can be read"),
+            fix,
+        )
+    } else if readable < total {
+        check(
+            "Input devices",
+            CheckStatus::Warning,
+            &format!("{readable} of {total} devices in /dev/input can be read"),
+            fix,
+        )
+    } else {
+        check(
+            "Input devices",
+            CheckStatus::Ok,
+            &format!("All {total} devices in /dev/input can be read"),
+            "",
+        )
+    }
+}
+
+fn check_xdg() -> Vec<Check> {
+    let mut out = Vec::new();
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").map(PathBuf::from);
+    out.push(match runtime_dir {
+        Ok(dir) if dir.is_dir() => check(
+            "XDG runtime directory",
+            CheckStatus::Ok,
+            &dir.display().to_string(),
+            "",
+        ),
+        _ => check(
+            "XDG runtime directory",
+            CheckStatus::Error,
+            "XDG_RUNTIME_DIR is not set or doesn't exist",
+            "Start PartyDeck from a regular desktop session; gamescope and D-Bus need it.",
+        ),
+    });
+
+    let test_file = PATH_PARTY.join(".doctor");
+    let writable = std::fs::create_dir_all(&*PATH_PARTY).is_ok()
+        && std::fs::write(&test_file, "").is_ok()
+        && std::fs::remove_file(&test_file).is_ok();
+    out.push(match writable {
+        true => check(
+            "Data directory",
+            CheckStatus::Ok,
+            &PATH_PARTY.display().to_string(),
+            "",
+        ),
+        false => check(
+            "Data directory",
+            CheckStatus::Error,
+            &format!("{} is not writable", PATH_PARTY.display()),
+            "Check the folder's permissions, or point XDG_DATA_HOME somewhere writable.",
+        ),
+    });
+    out
+}
+
+pub fn run_checks(cfg: &PartyConfig) -> Vec<Check> {
+    let mut out = Vec::new();
+    out.extend(check_gamescope(cfg));
+    out.extend(check_bwrap());
+    out.push(check_kwin(cfg));
+    out.extend(check_steam(cfg));
+    out.extend(check_dependencies());
+    out.push(check_input());
+    out.extend(check_xdg());
+    out
+}
+
+// Prints the checks for "partydeck doctor", returning whether everything needed to launch is there
+pub fn print_checks(checks: &[Check]) -> bool {
+    for c in checks {
+        println!("[{:^4}] {}: {}", c.status.label(), c.name, c.detail);
+        if !c.fix.is_empty() && c.status != CheckStatus::Ok {
+            println!("       Fix: {}", c.fix);
+        }
+    }
+    !checks.iter().any(|c| c.status == CheckStatus::Error)
+}
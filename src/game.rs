@@ -1,5 +1,6 @@
 use crate::handler::{Handler, install_handler_from_file, scan_handlers};
 use crate::paths::*;
+use crate::trust::remove_handler_trust;
 use crate::util::SanitizePath;
 
 use eframe::egui::{self, ImageSource};
@@ -114,9 +115,11 @@ pub fn add_game() -> Result<(), Box<dyn Error>> {
         return Ok(());
     }
 
-    // Check if the file has a valid extension (pdh, exe, or no extension)
+    // Check if the file has a valid extension (pdh, exe, AppImage, or no extension)
     let extension = file.as_ref().unwrap().extension().unwrap_or_default();
-    if !["pdh", "exe", "sh", ""].contains(&extension.to_str().unwrap_or("")) {
+    if !["pdh", "exe", "sh", "appimage", ""]
+        .contains(&extension.to_str().unwrap_or("").to_lowercase().as_str())
+    {
         return Err("Invalid file type!".into());
     }
 
@@ -191,6 +194,7 @@ pub fn remove_game(game: &Game) -> Result<(), Box<dyn Error>> {
 
         Game::HandlerRef(h) => {
             std::fs::remove_dir_all(h.path_handler.clone())?;
+            remove_handler_trust(&h.uid);
         }
     }
 
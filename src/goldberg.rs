@@ -0,0 +1,578 @@
+use crate::handler::Handler;
+use crate::paths::*;
+use crate::templates::{ini_get, ini_set};
+use crate::util::sha256_file;
+
+use serde_json::Value;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+// Language names as Steam (and so Goldberg) spells them
+pub const LANGUAGES: [&str; 29] = [
+    "english",
+    "arabic",
+    "brazilian",
+    "bulgarian",
+    "czech",
+    "danish",
+    "dutch",
+    "finnish",
+    "french",
+    "german",
+    "greek",
+    "hungarian",
+    "indonesian",
+    "italian",
+    "japanese",
+    "koreana",
+    "latam",
+    "norwegian",
+    "polish",
+    "portuguese",
+    "romanian",
+    "russian",
+    "schinese",
+    "spanish",
+    "swedish",
+    "tchinese",
+    "thai",
+    "turkish",
+    "ukrainian",
+];
+
+// SteamID64 of account id 0 in the public universe, as an individual account on the desktop instance
+const STEAM_ID64_BASE: u64 = 76561197960265728;
+
+// Derives the account id from the profile name with 32-bit FNV-1a, which unlike std's hasher
+// is guaranteed to give the same result forever, so a profile always keeps its ID
+pub fn steam_id_for_name(name: &str) -> String {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in name.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    // Account id 0 is reserved as invalid
+    let account_id = hash.max(1);
+    (STEAM_ID64_BASE + account_id as u64).to_string()
+}
+
+// The ID to store for a profile: the one derived from its name, unless another profile (e.g. one
+// renamed away from the same name) already has it, in which case a counter is added to the name
+pub fn unique_steam_id(name: &str) -> String {
+    let taken: Vec<String> = std::fs::read_dir(PATH_PARTY.join("profiles"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|profname| profname != name)
+                .map(|profname| GoldbergProfile::load(&profname).steam_id)
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut id = steam_id_for_name(name);
+    let mut n = 1;
+    while taken.contains(&id) {
+        n += 1;
+        id = steam_id_for_name(&format!("{name}#{n}"));
+    }
+    id
+}
+
+pub fn is_valid_steam_id(id: &str) -> bool {
+    match id.parse::<u64>() {
+        Ok(id) => id > STEAM_ID64_BASE && id >> 32 == STEAM_ID64_BASE >> 32,
+        Err(_) => false,
+    }
+}
+
+// Accepts a SteamID64 or a SteamID3 like [U:1:12345], as shown by Steam and sites like steamid.io
+pub fn parse_steam_id(input: &str) -> Option<String> {
+    let input = input.trim();
+    if is_valid_steam_id(input) {
+        return Some(input.to_string());
+    }
+    let account_id = input
+        .strip_prefix("[U:1:")
+        .and_then(|s| s.strip_suffix(']'))
+        .and_then(|s| s.parse::<u32>().ok())
+        .filter(|id| *id > 0)?;
+    Some((STEAM_ID64_BASE + account_id as u64).to_string())
+}
+
+// Gives profiles from older versions, whose IDs were random and not valid SteamID64s, a proper
+// ID. Save files and folders named after the old ID get a symlink under the new one, and the
+// mapping is kept in steam/steamid_migration.json.
+pub fn migrate_profile_steam_ids() {
+    let Ok(entries) = std::fs::read_dir(PATH_PARTY.join("profiles")) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let profname = entry.file_name().to_string_lossy().to_string();
+        if profname.starts_with('.') {
+            continue;
+        }
+        let mut profile = GoldbergProfile::load(&profname);
+        if is_valid_steam_id(&profile.steam_id) {
+            continue;
+        }
+
+        let old_id = profile.steam_id.clone();
+        profile.steam_id = unique_steam_id(&profname);
+        println!(
+            "Migrating profile {profname} from Steam ID {old_id} to {}",
+            profile.steam_id
+        );
+        if let Err(err) = migrate_profile(&profname, &profile, &old_id) {
+            println!("Couldn't migrate profile {profname}: {err}");
+        }
+    }
+}
+
+fn migrate_profile(
+    profname: &str,
+    profile: &GoldbergProfile,
+    old_id: &str,
+) -> Result<(), Box<dyn Error>> {
+    let path_profile = PATH_PARTY.join("profiles").join(profname);
+
+    if !old_id.is_empty() {
+        let mapping = serde_json::json!({ "old": old_id, "new": profile.steam_id });
+        std::fs::write(
+            path_profile.join("steam/steamid_migration.json"),
+            serde_json::to_string_pretty(&mapping)?,
+        )?;
+
+        // Collect first, so the walk doesn't pick up the links being made
+        let renamed: Vec<(PathBuf, PathBuf)> = walkdir::WalkDir::new(&path_profile)
+            .min_depth(1)
+            .follow_links(false)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().to_string();
+                if !name.contains(old_id) {
+                    return None;
+                }
+                let new_path = e
+                    .path()
+                    .with_file_name(name.replace(old_id, &profile.steam_id));
+                Some((e.path().to_path_buf(), new_path))
+            })
+            .collect();
+        for (old_path, new_path) in renamed {
+            if new_path.symlink_metadata().is_ok() {
+                continue;
+            }
+            if let Some(old_name) = old_path.file_name() {
+                std::os::unix::fs::symlink(old_name, &new_path)?;
+            }
+        }
+    }
+
+    profile.save(profname)?;
+    Ok(())
+}
+
+// The per-player half of Goldberg's settings. Each profile's steam folder is bound over the
+// game's goldbergsave folder, and Goldberg reads settings/configs.user.ini from there.
+#[derive(Clone)]
+pub struct GoldbergProfile {
+    pub name: String,
+    pub steam_id: String,
+    pub language: String,
+    pub country: String,
+}
+
+fn path_profile_settings(profname: &str) -> PathBuf {
+    PATH_PARTY
+        .join("profiles")
+        .join(profname)
+        .join("steam/settings")
+}
+
+impl GoldbergProfile {
+    pub fn load(profname: &str) -> Self {
+        let contents =
+            std::fs::read_to_string(path_profile_settings(profname).join("configs.user.ini"))
+                .unwrap_or_default();
+        let get = |key: &str| ini_get(&contents, "user::general", key).unwrap_or_default();
+        let or_default = |value: String, default: &str| match value.is_empty() {
+            true => default.to_string(),
+            false => value,
+        };
+
+        Self {
+            name: or_default(get("account_name"), profname),
+            steam_id: get("account_steamid"),
+            language: or_default(get("language"), "english"),
+            country: or_default(get("ip_country"), "US"),
+        }
+    }
+
+    // Only touches our keys, so anything else the user put in the file stays
+    pub fn save(&self, profname: &str) -> Result<(), std::io::Error> {
+        let path_settings = path_profile_settings(profname);
+        std::fs::create_dir_all(&path_settings)?;
+        let path_ini = path_settings.join("configs.user.ini");
+
+        let mut contents = std::fs::read_to_string(&path_ini).unwrap_or_default();
+        for (key, value) in [
+            ("account_name", &self.name),
+            ("account_steamid", &self.steam_id),
+            ("language", &self.language),
+            ("ip_country", &self.country),
+        ] {
+            contents = ini_set(&contents, "user::general", key, value);
+        }
+        std::fs::write(path_ini, contents)
+    }
+}
+
+pub fn profile_avatar(profname: &str) -> Option<PathBuf> {
+    ["png", "jpg"]
+        .iter()
+        .map(|ext| path_profile_settings(profname).join(format!("account_avatar.{ext}")))
+        .find(|path| path.exists())
+}
+
+pub fn set_profile_avatar(profname: &str, src: &PathBuf) -> Result<(), Box<dyn Error>> {
+    let ext = src
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    let ext = match ext.as_str() {
+        "png" => "png",
+        "jpg" | "jpeg" => "jpg",
+        _ => return Err("Avatar must be a PNG or JPG image".into()),
+    };
+    if let Some(old) = profile_avatar(profname) {
+        std::fs::remove_file(old)?;
+    }
+    let path_settings = path_profile_settings(profname);
+    std::fs::create_dir_all(&path_settings)?;
+    std::fs::copy(src, path_settings.join(format!("account_avatar.{ext}")))?;
+    Ok(())
+}
+
+// Writes the configs.*.ini files for the options the handler sets. Files are left alone when
+// the handler doesn't use any of their options, so ones shipped in copy_to_symdir still work.
+pub fn write_goldberg_configs(h: &Handler, steam_settings: &Path) -> Result<(), Box<dyn Error>> {
+    let update = |file: &str, entries: &[(&str, String, String)]| -> Result<(), Box<dyn Error>> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let path = steam_settings.join(file);
+        let mut contents = std::fs::read_to_string(&path).unwrap_or_default();
+        for (section, key, value) in entries {
+            contents = ini_set(&contents, section, key, value);
+        }
+        std::fs::write(path, contents)?;
+        Ok(())
+    };
+
+    let mut main = Vec::new();
+    if let Some(lan_only) = h.goldberg_lan_only {
+        main.push((
+            "main::connectivity",
+            "disable_lan_only".to_string(),
+            match lan_only {
+                true => "0".to_string(),
+                false => "1".to_string(),
+            },
+        ));
+    }
+    if h.goldberg_listen_port > 0 {
+        main.push((
+            "main::connectivity",
+            "listen_port".to_string(),
+            h.goldberg_listen_port.to_string(),
+        ));
+    }
+    update("configs.main.ini", &main)?;
+
+    let mut app = Vec::new();
+    if !h.goldberg_dlcs.is_empty() {
+        app.push(("app::dlcs", "unlock_all".to_string(), "0".to_string()));
+        for (appid, name) in &h.goldberg_dlcs {
+            app.push(("app::dlcs", appid.clone(), name.clone()));
+        }
+    }
+    update("configs.app.ini", &app)?;
+
+    if h.goldberg_overlay {
+        update(
+            "configs.overlay.ini",
+            &[(
+                "overlay::general",
+                "enable_experimental_overlay".to_string(),
+                "1".to_string(),
+            )],
+        )?;
+    }
+
+    // Schemas shipped with the handler; Goldberg expects them under these names
+    for (key, path, dest) in [
+        (
+            "goldberg.achievements",
+            &h.goldberg_achievements,
+            "achievements.json",
+        ),
+        ("goldberg.stats", &h.goldberg_stats, "stats.txt"),
+    ] {
+        if path.is_empty() {
+            continue;
+        }
+        let src = h.handler_file(key, path);
+        if !src.exists() {
+            return Err(format!("Goldberg schema not found: {}", src.display()).into());
+        }
+        std::fs::copy(src, steam_settings.join(dest))?;
+    }
+
+    Ok(())
+}
+
+fn path_interface_cache() -> PathBuf {
+    PATH_PARTY.join("cache/interfaces")
+}
+
+// index.json maps handler uids to the hash of the steam_api they last used
+fn interface_index() -> serde_json::Map<String, Value> {
+    std::fs::read_to_string(path_interface_cache().join("index.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn record_interfaces(uid: &str, hash: &str) -> Result<(), Box<dyn Error>> {
+    let mut index = interface_index();
+    index.insert(uid.to_string(), Value::String(hash.to_string()));
+    std::fs::write(
+        path_interface_cache().join("index.json"),
+        serde_json::to_string_pretty(&index)?,
+    )?;
+    Ok(())
+}
+
+// The interfaces last used for a handler, for showing on the game page
+pub fn cached_interfaces(uid: &str) -> Option<String> {
+    let hash = interface_index().get(uid)?.as_str()?.to_string();
+    std::fs::read_to_string(path_interface_cache().join(format!("{hash}.txt"))).ok()
+}
+
+// Puts steam_interfaces.txt in steam_settings. Results are cached by the hash of the game's
+// steam_api, so the generator only runs when the game ships a steam_api we haven't seen.
+// If it fails, the handler's own steam_interfaces.txt or the last one used for it are used instead.
+pub fn generate_interfaces(
+    h: &Handler,
+    steamdll: &PathBuf,
+    steam_settings: &PathBuf,
+) -> Result<(), Box<dyn Error>> {
+    let path_cache = path_interface_cache();
+    std::fs::create_dir_all(&path_cache)?;
+    let dest = steam_settings.join("steam_interfaces.txt");
+
+    let hash = sha256_file(steamdll);
+    if let Ok(hash) = &hash {
+        let cached = path_cache.join(format!("{hash}.txt"));
+        if cached.exists() {
+            println!("Using cached interfaces for {}", steamdll.display());
+            std::fs::copy(&cached, &dest)?;
+            record_interfaces(&h.uid, hash)?;
+            return Ok(());
+        }
+
+        let gen_interfaces = match &h.is32bit {
+            true => PATH_GOLDBERG.join("generate_interfaces_x32"),
+            false => PATH_GOLDBERG.join("generate_interfaces_x64"),
+        };
+        // Anything already there, e.g. from the handler's copy_to_symdir, would pass for output
+        if dest.symlink_metadata().is_ok() {
+            std::fs::remove_file(&dest)?;
+        }
+        let status = std::process::Command::new(gen_interfaces)
+            .arg(steamdll)
+            .current_dir(steam_settings)
+            .status();
+        match status {
+            Ok(status) if status.success() && dest.metadata().is_ok_and(|m| m.len() > 0) => {
+                std::fs::copy(&dest, &cached)?;
+                record_interfaces(&h.uid, hash)?;
+                return Ok(());
+            }
+            Ok(status) => println!("Generate interfaces failed ({status})"),
+            Err(err) => println!("Couldn't run generate interfaces: {err}"),
+        }
+    } else {
+        println!("Couldn't read {}", steamdll.display());
+    }
+
+    let handler_interfaces = h.path_handler.join("steam_interfaces.txt");
+    if handler_interfaces.exists() {
+        println!("Using steam_interfaces.txt from the {} handler", h.uid);
+        std::fs::copy(handler_interfaces, &dest)?;
+        return Ok(());
+    }
+    if let Some(interfaces) = cached_interfaces(&h.uid) {
+        println!("Using the last interfaces generated for {}", h.uid);
+        std::fs::write(&dest, interfaces)?;
+        return Ok(());
+    }
+
+    Err("Generate interfaces failed, and there's no steam_interfaces.txt to fall back on".into())
+}
+
+pub struct Achievement {
+    pub display_name: String,
+    pub description: String,
+    pub earned: bool,
+    pub earned_time: u64,
+}
+
+pub struct GameStats {
+    pub achievements: Vec<Achievement>,
+    pub stats: Vec<(String, String)>,
+}
+
+// Schema strings are either plain, or an object keyed by language
+fn localized(value: &Value, language: &str) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Object(map) => map
+            .get(language)
+            .or_else(|| map.get("english"))
+            .or_else(|| map.values().next())
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        _ => String::new(),
+    }
+}
+
+// Reads what Goldberg recorded for a profile in <profile>/steam/<appid>, using the handler's
+// schemas for names and stat types. None if the profile hasn't played the game.
+pub fn load_game_stats(profname: &str, h: &Handler) -> Option<GameStats> {
+    let appid = h.steam_appid.as_ref()?;
+    let path_save = PATH_PARTY
+        .join("profiles")
+        .join(profname)
+        .join("steam")
+        .join(appid);
+    if !path_save.exists() {
+        return None;
+    }
+    let language = GoldbergProfile::load(profname).language;
+
+    let earned: Value = std::fs::read_to_string(path_save.join("achievements.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    let schema: Value =
+        std::fs::read_to_string(h.handler_file("goldberg.achievements", &h.goldberg_achievements))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+    let mut achievements = Vec::new();
+    match schema.as_array() {
+        Some(schema) => {
+            for ach in schema {
+                let name = ach["name"].as_str().unwrap_or_default();
+                let hidden = ach["hidden"].as_str() == Some("1") || ach["hidden"] == 1;
+                let is_earned = earned[name]["earned"].as_bool().unwrap_or_default();
+                achievements.push(Achievement {
+                    display_name: localized(&ach["displayName"], &language),
+                    description: match hidden && !is_earned {
+                        true => "Hidden achievement".to_string(),
+                        false => localized(&ach["description"], &language),
+                    },
+                    earned: is_earned,
+                    earned_time: earned[name]["earned_time"].as_u64().unwrap_or_default(),
+                });
+            }
+        }
+        // Without a schema, all we can show are the internal names of what's been earned
+        None => {
+            if let Some(map) = earned.as_object() {
+                for (name, state) in map {
+                    achievements.push(Achievement {
+                        display_name: name.clone(),
+                        description: String::new(),
+                        earned: state["earned"].as_bool().unwrap_or_default(),
+                        earned_time: state["earned_time"].as_u64().unwrap_or_default(),
+                    });
+                }
+            }
+        }
+    }
+
+    // stats.txt lines look like "name=type=default", with type int, float or avgrate
+    let stat_types: Vec<(String, String)> =
+        std::fs::read_to_string(h.handler_file("goldberg.stats", &h.goldberg_stats))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.trim().split('=');
+                Some((parts.next()?.to_string(), parts.next()?.to_string()))
+            })
+            .collect();
+
+    let mut stats = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(path_save.join("stats")) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Ok(bytes) = std::fs::read(entry.path()) else {
+                continue;
+            };
+            let Ok(raw): Result<[u8; 4], _> = bytes.get(..4).unwrap_or_default().try_into() else {
+                continue;
+            };
+            let stat_type = stat_types
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(&name))
+                .map(|(_, t)| t.as_str())
+                .unwrap_or("int");
+            let value = match stat_type {
+                "float" | "avgrate" => f32::from_le_bytes(raw).to_string(),
+                _ => i32::from_le_bytes(raw).to_string(),
+            };
+            stats.push((name, value));
+        }
+    }
+    stats.sort();
+
+    Some(GameStats {
+        achievements,
+        stats,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steam_id_for_name_is_stable() {
+        // Changing this would change the Steam ID of every existing profile
+        assert_eq!(steam_id_for_name("Alice"), "76561198712980871");
+        assert!(is_valid_steam_id(&steam_id_for_name("Alice")));
+        assert_ne!(steam_id_for_name("Alice"), steam_id_for_name("Bob"));
+    }
+
+    #[test]
+    fn parse_steam_id_formats() {
+        assert_eq!(
+            parse_steam_id(" 76561198712980871 ").as_deref(),
+            Some("76561198712980871")
+        );
+        assert_eq!(
+            parse_steam_id("[U:1:12345]").as_deref(),
+            Some("76561197960278073")
+        );
+        assert_eq!(parse_steam_id("[U:1:0]"), None);
+        assert_eq!(parse_steam_id("76561197960265728"), None);
+        assert_eq!(parse_steam_id("12345"), None);
+        assert_eq!(parse_steam_id("Alice"), None);
+    }
+}
@@ -1,54 +1,91 @@
+use crate::goldberg::{generate_interfaces, write_goldberg_configs};
 use crate::paths::*;
+use crate::templates::ProfileFile;
+use crate::trust::*;
 use crate::util::*;
 
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Clone)]
 pub struct Handler {
     // Members that are determined by context
     pub path_handler: PathBuf,
     pub img_paths: Vec<PathBuf>,
+    pub trust: HandlerTrust,
+    // handler.json after merging in any base handlers/templates it extends
+    pub resolved_json: Value,
 
     pub uid: String,
     pub name: String,
     pub author: String,
     pub version: String,
     pub info: String,
+    pub hooks: String,
 
     pub symlink_dir: bool,
     pub win: bool,
     pub runtime: String,
     pub is32bit: bool,
     pub exec: String,
+    // Flatpak app ID, run instead of exec when set
+    pub flatpak: String,
+    pub flatpak_filesystems: Vec<String>,
+    pub flatpak_persist: Vec<String>,
     pub args: Vec<String>,
+    pub port_base: u16,
     pub copy_instead_paths: Vec<String>,
     pub remove_paths: Vec<String>,
     pub dll_overrides: Vec<String>,
+    // Per-DLL override modes, e.g. ("d3d9", "n,b") or ("xinput1_3", "") to disable it
+    pub dll_modes: Vec<(String, String)>,
+    // Winetricks verbs applied once per prefix, e.g. vcrun2019 or corefonts
+    pub wine_verbs: Vec<String>,
+    pub env: Vec<(String, String)>,
+
+    pub gamescope_args: Vec<String>,
+    pub gamescope_fsr: bool,
+    pub gamescope_fps_limit: u32,
+    pub gamescope_nested_width: u32,
+    pub gamescope_nested_height: u32,
 
     pub path_goldberg: String,
     pub steam_appid: Option<String>,
     pub coldclient: bool,
+    // None leaves Goldberg's default (LAN only) alone
+    pub goldberg_lan_only: Option<bool>,
+    pub goldberg_listen_port: u16,
+    pub goldberg_dlcs: Vec<(String, String)>,
+    pub goldberg_achievements: String,
+    pub goldberg_stats: String,
+    pub goldberg_overlay: bool,
 
     pub win_unique_appdata: bool,
     pub win_unique_documents: bool,
     pub linux_unique_localshare: bool,
     pub linux_unique_config: bool,
     pub game_unique_paths: Vec<String>,
+    // Folders under _AppData, _Documents, _share or _config holding the game's data, for
+    // importing and exporting existing saves
+    pub import_paths: Vec<String>,
+    pub profile_files: Vec<ProfileFile>,
 }
 
 impl Handler {
     pub fn new(json_path: &PathBuf) -> Result<Self, Box<dyn Error>> {
-        let file = File::open(json_path)?;
-        let reader = BufReader::new(file);
-        let json: Value = serde_json::from_reader(reader)?;
+        let mut bases = Vec::new();
+        let json = load_handler_json(json_path, &mut Vec::new(), &mut bases)?;
 
         let mut handler = Self {
             path_handler: PathBuf::new(),
             img_paths: Vec::new(),
+            trust: HandlerTrust::Unsigned,
+            resolved_json: json.clone(),
 
             uid: json["handler.uid"].as_str().unwrap_or_default().to_string(),
             name: json["handler.name"]
@@ -67,6 +104,11 @@ impl Handler {
                 .as_str()
                 .unwrap_or_default()
                 .to_string(),
+            hooks: json["handler.hooks"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string()
+                .sanitize_path(),
 
             symlink_dir: json["game.symlink_dir"].as_bool().unwrap_or_default(),
             win: json["game.win"].as_bool().unwrap_or_default(),
@@ -80,6 +122,31 @@ impl Handler {
                 .unwrap_or_default()
                 .to_string()
                 .sanitize_path(),
+            flatpak: json["game.flatpak"]
+                .as_str()
+                .unwrap_or_default()
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || "._-".contains(*c))
+                .collect(),
+            flatpak_filesystems: json["game.flatpak_filesystems"]
+                .as_array()
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str())
+                        .filter(|v| !v.is_empty() && !v.contains(['"', '$', '`', '\\']))
+                        .map(|v| v.to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            flatpak_persist: json["game.flatpak_persist"]
+                .as_array()
+                .map(|arr| {
+                    arr.iter()
+                        .map(|v| v.as_str().unwrap_or_default().to_string().sanitize_path())
+                        .filter(|v| !v.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
             args: json["game.args"]
                 .as_array()
                 .map(|arr| {
@@ -88,6 +155,10 @@ impl Handler {
                         .collect()
                 })
                 .unwrap_or_default(),
+            port_base: json["game.port_base"]
+                .as_u64()
+                .and_then(|p| u16::try_from(p).ok())
+                .unwrap_or_default(),
             copy_instead_paths: json["game.copy_instead_paths"]
                 .as_array()
                 .map(|arr| {
@@ -112,6 +183,69 @@ impl Handler {
                         .collect()
                 })
                 .unwrap_or_default(),
+            dll_modes: json["wine.dll_overrides"]
+                .as_object()
+                .map(|map| {
+                    map.iter()
+                        .filter_map(|(dll, mode)| {
+                            let mode = match mode.as_str().unwrap_or_default() {
+                                "disabled" => "",
+                                m @ ("n" | "b" | "n,b" | "b,n") => m,
+                                m => {
+                                    println!("Ignoring unknown override mode {m} for {dll}");
+                                    return None;
+                                }
+                            };
+                            Some((dll.clone(), mode.to_string()))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            wine_verbs: json["wine.verbs"]
+                .as_array()
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str())
+                        .filter(|v| {
+                            !v.is_empty()
+                                && v.chars()
+                                    .all(|c| c.is_ascii_alphanumeric() || "_-=.".contains(c))
+                        })
+                        .map(|v| v.to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            env: json["game.env"]
+                .as_object()
+                .map(|map| {
+                    map.iter()
+                        .map(|(k, v)| {
+                            let value = match v {
+                                Value::String(s) => s.clone(),
+                                other => other.to_string(),
+                            };
+                            (k.clone(), value)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+
+            gamescope_args: json["gamescope.args"]
+                .as_array()
+                .map(|arr| {
+                    arr.iter()
+                        .map(|v| v.as_str().unwrap_or_default().to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            gamescope_fsr: json["gamescope.fsr"].as_bool().unwrap_or_default(),
+            gamescope_fps_limit: json["gamescope.framerate_limit"]
+                .as_u64()
+                .unwrap_or_default() as u32,
+            gamescope_nested_width: json["gamescope.nested_width"].as_u64().unwrap_or_default()
+                as u32,
+            gamescope_nested_height: json["gamescope.nested_height"].as_u64().unwrap_or_default()
+                as u32,
 
             path_goldberg: json["steam.api_path"]
                 .as_str()
@@ -122,6 +256,34 @@ impl Handler {
                 .as_str()
                 .and_then(|s| Some(s.to_string())),
             coldclient: json["steam.gb_coldclient"].as_bool().unwrap_or_default(),
+            goldberg_lan_only: json["goldberg.lan_only"].as_bool(),
+            goldberg_listen_port: json["goldberg.listen_port"]
+                .as_u64()
+                .and_then(|p| u16::try_from(p).ok())
+                .unwrap_or_default(),
+            goldberg_dlcs: json["goldberg.dlcs"]
+                .as_object()
+                .map(|map| {
+                    map.iter()
+                        .filter(|(appid, _)| appid.chars().all(|c| c.is_ascii_digit()))
+                        .map(|(appid, name)| {
+                            let name = name.as_str().unwrap_or_default();
+                            (appid.clone(), name.replace(['\n', '\r'], " "))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            goldberg_achievements: json["goldberg.achievements"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string()
+                .sanitize_path(),
+            goldberg_stats: json["goldberg.stats"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string()
+                .sanitize_path(),
+            goldberg_overlay: json["goldberg.overlay"].as_bool().unwrap_or_default(),
 
             win_unique_appdata: json["profiles.unique_appdata"]
                 .as_bool()
@@ -141,6 +303,19 @@ impl Handler {
                         .collect()
                 })
                 .unwrap_or_default(),
+            import_paths: json["profiles.import_paths"]
+                .as_array()
+                .map(|arr| {
+                    arr.iter()
+                        .map(|v| v.as_str().unwrap_or_default().to_string().sanitize_path())
+                        .filter(|v| !v.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            profile_files: json["profiles.files"]
+                .as_array()
+                .map(|arr| arr.iter().filter_map(ProfileFile::from_json).collect())
+                .unwrap_or_default(),
         };
 
         if !handler.uid.chars().all(char::is_alphanumeric) {
@@ -152,10 +327,27 @@ impl Handler {
             .ok_or_else(|| "Invalid path")?
             .to_path_buf();
         handler.img_paths = handler.get_imgs();
+        handler.trust = read_handler_trust(&handler.uid, &handler.path_handler);
+        // The signature only covers this handler's own files, so whatever it extends
+        // has to be trusted as well
+        if let HandlerTrust::Trusted(_) = handler.trust
+            && let Some((name, _)) = bases.iter().find(|(_, path)| !is_trusted_base(path))
+        {
+            handler.trust = HandlerTrust::UntrustedBase(name.clone());
+        }
 
         Ok(handler)
     }
 
+    // A file shipped with the handler, looked up in the folder of whichever handler or
+    // template set the key
+    pub fn handler_file(&self, key: &str, path: &str) -> PathBuf {
+        match self.resolved_json[format!("{key}.dir")].as_str() {
+            Some(dir) => PathBuf::from(dir).join(path),
+            None => self.path_handler.join(path),
+        }
+    }
+
     pub fn display(&self) -> &str {
         if self.name.is_empty() {
             self.uid.as_str()
@@ -197,6 +389,153 @@ impl Handler {
     }
 }
 
+// Finds a base handler by name: shared templates in handlers/_templates take priority
+// over installed handlers with the same uid.
+fn find_base_handler(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!("Invalid base handler name \"{name}\"").into());
+    }
+    let template = PATH_PARTY
+        .join("handlers/_templates")
+        .join(format!("{name}.json"));
+    if template.exists() {
+        return Ok(template);
+    }
+    let handler = PATH_PARTY.join("handlers").join(name).join("handler.json");
+    if handler.exists() {
+        return Ok(handler);
+    }
+    Err(format!("Base handler \"{name}\" not found").into())
+}
+
+// Templates in handlers/_templates are never signed; installed handlers are trusted
+// if their own signature is
+fn is_trusted_base(base_path: &Path) -> bool {
+    let Some(dir) = base_path.parent() else {
+        return false;
+    };
+    if base_path.file_name().unwrap_or_default() != "handler.json" {
+        return false;
+    }
+    let uid = dir.file_name().unwrap_or_default().to_string_lossy();
+    matches!(
+        read_handler_trust(&uid, &dir.to_path_buf()),
+        HandlerTrust::Trusted(_)
+    )
+}
+
+// Keys holding a file path relative to the handler folder
+const HANDLER_FILE_KEYS: [&str; 2] = ["goldberg.achievements", "goldberg.stats"];
+
+// Records the folder of the handler.json or template next to each file path it sets, as
+// "<key>.dir" or "dir" in profiles.files entries, so inherited paths still point into the
+// base's folder after merging
+fn tag_handler_dirs(json: &mut Value, dir: &Path) {
+    let Value::Object(map) = json else {
+        return;
+    };
+    let dir = Value::String(dir.to_string_lossy().to_string());
+    for key in HANDLER_FILE_KEYS {
+        let key_dir = format!("{key}.dir");
+        match map.contains_key(key) {
+            true => map.insert(key_dir, dir.clone()),
+            false => map.remove(&key_dir),
+        };
+    }
+    if let Some(Value::Array(files)) = map.get_mut("profiles.files") {
+        for file in files.iter_mut().filter_map(|f| f.as_object_mut()) {
+            file.insert("dir".to_string(), dir.clone());
+        }
+    }
+}
+
+// Objects are merged key by key, arrays are appended unless the key is listed in
+// the child's handler.replace, and anything else is overridden by the child.
+fn merge_handler_json(base: &mut Value, child: &Value, replace: &[String], key: &str) {
+    match (base, child) {
+        (Value::Object(base_map), Value::Object(child_map)) => {
+            for (k, v) in child_map {
+                let path = match key.is_empty() {
+                    true => k.clone(),
+                    false => format!("{key}.{k}"),
+                };
+                match base_map.get_mut(k) {
+                    Some(base_v) => merge_handler_json(base_v, v, replace, &path),
+                    None => {
+                        base_map.insert(k.clone(), v.clone());
+                    }
+                }
+            }
+        }
+        (Value::Array(base_arr), Value::Array(child_arr)) if !replace.iter().any(|r| r == key) => {
+            base_arr.extend(child_arr.iter().cloned());
+        }
+        (base, child) => *base = child.clone(),
+    }
+}
+
+// Reads a handler.json and resolves handler.extends, which can name a single base or a list
+// of them (applied in order). Handler metadata (handler.*) is never inherited.
+// Every base it pulls in, directly or not, is added to bases_used.
+fn load_handler_json(
+    json_path: &PathBuf,
+    visited: &mut Vec<PathBuf>,
+    bases_used: &mut Vec<(String, PathBuf)>,
+) -> Result<Value, Box<dyn Error>> {
+    if visited.contains(json_path) {
+        return Err(format!("Handler inheritance loop at {}", json_path.display()).into());
+    }
+    visited.push(json_path.clone());
+
+    let file = File::open(json_path)?;
+    let mut json: Value = serde_json::from_reader(BufReader::new(file))?;
+    if let Some(dir) = json_path.parent() {
+        tag_handler_dirs(&mut json, dir);
+    }
+
+    let bases: Vec<String> = match &json["handler.extends"] {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(arr) => arr
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    };
+    if bases.is_empty() {
+        visited.pop();
+        return Ok(json);
+    }
+
+    let replace: Vec<String> = json["handler.replace"]
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut resolved = Value::Object(serde_json::Map::new());
+    for base in &bases {
+        let base_path = find_base_handler(base)?;
+        println!("{} extends {}", json_path.display(), base_path.display());
+        let mut base_json = load_handler_json(&base_path, visited, bases_used)?;
+        bases_used.push((base.clone(), base_path));
+        if let Value::Object(map) = &mut base_json {
+            map.retain(|k, _| !k.starts_with("handler."));
+        }
+        merge_handler_json(&mut resolved, &base_json, &[], "");
+    }
+    merge_handler_json(&mut resolved, &json, &replace, "");
+
+    visited.pop();
+    Ok(resolved)
+}
+
 pub fn scan_handlers() -> Vec<Handler> {
     let mut out: Vec<Handler> = Vec::new();
     let handlers_path = PATH_PARTY.join("handlers");
@@ -235,6 +574,28 @@ pub fn install_handler_from_file(file: &PathBuf) -> Result<(), Box<dyn Error>> {
         return Err("Handler not valid!".into());
     }
 
+    // Handlers can ship binaries that run inside the game directory, so make sure
+    // the user knows what they're installing if we can't vouch for the author
+    let trust = verify_handler_file(file);
+    let warning = match &trust {
+        HandlerTrust::Trusted(_) => None,
+        HandlerTrust::Unsigned => Some(
+            "This handler is not signed. Handlers can include programs that run alongside your games, so only install handlers from sources you trust. Install anyway?",
+        ),
+        HandlerTrust::Untrusted | HandlerTrust::UntrustedBase(_) => Some(
+            "This handler is signed, but not by any author in your trusted keys list. Handlers can include programs that run alongside your games, so only install handlers from sources you trust. Install anyway?",
+        ),
+        HandlerTrust::Invalid | HandlerTrust::Modified => Some(
+            "This handler's signature is INVALID. The file may have been tampered with or corrupted. Install anyway?",
+        ),
+    };
+    if let Some(warning) = warning
+        && !yesno("Handler Warning", warning)
+    {
+        println!("Installation of {} cancelled", file.display());
+        return Ok(());
+    }
+
     let dir_handlers = PATH_PARTY.join("handlers");
     let dir_tmp = PATH_PARTY.join("tmp");
     if !dir_tmp.exists() {
@@ -264,15 +625,131 @@ pub fn install_handler_from_file(file: &PathBuf) -> Result<(), Box<dyn Error>> {
     copy_dir_recursive(&dir_tmp, &dir_handlers.join(uid), false, true)?;
     std::fs::remove_dir_all(&dir_tmp)?;
 
+    write_handler_trust(uid, &dir_handlers.join(uid), file, &trust)?;
+
     Ok(())
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+struct ManifestEntry {
+    size: u64,
+    mtime: i64,
+    // Directories are recorded so empty ones can be removed again; their size and mtime are 0
+    #[serde(default)]
+    dir: bool,
+}
+
+// Snapshot of the game folder a symlink folder was built from, in gamesyms/.manifests/<uid>.json
+#[derive(Serialize, Deserialize, Default)]
+struct SymlinkManifest {
+    handler_version: String,
+    files: BTreeMap<String, ManifestEntry>,
+}
+
+pub fn path_symlink_manifest(uid: &str) -> PathBuf {
+    PATH_PARTY
+        .join("gamesyms/.manifests")
+        .join(format!("{uid}.json"))
+}
+
+// Every file, symlink and directory under the game root, keyed by relative path
+fn scan_game_tree(path_root: &PathBuf) -> Result<BTreeMap<String, ManifestEntry>, Box<dyn Error>> {
+    let mut out = BTreeMap::new();
+    for entry in walkdir::WalkDir::new(path_root)
+        .min_depth(1)
+        .follow_links(false)
+    {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            let rel = entry.path().strip_prefix(path_root)?;
+            let dir = ManifestEntry {
+                dir: true,
+                ..Default::default()
+            };
+            out.insert(rel.to_string_lossy().to_string(), dir);
+            continue;
+        }
+        let meta = entry.path().symlink_metadata()?;
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let rel = entry.path().strip_prefix(path_root)?;
+        out.insert(
+            rel.to_string_lossy().to_string(),
+            ManifestEntry {
+                size: meta.len(),
+                mtime,
+                dir: false,
+            },
+        );
+    }
+    Ok(out)
+}
+
+fn is_under(rel: &str, paths: &[String]) -> bool {
+    paths
+        .iter()
+        .filter(|p| !p.is_empty())
+        .any(|p| rel == p || rel.starts_with(&format!("{p}/")))
+}
+
+fn is_steam_api(h: &Handler, rel: &str) -> bool {
+    let (dir, file) = rel.rsplit_once('/').unwrap_or(("", rel));
+    dir == h.path_goldberg.trim_end_matches('/')
+        && ["steam_api.dll", "steam_api64.dll", "libsteam_api.so"].contains(&file)
+}
+
 pub fn create_symlink_folder(h: &Handler) -> Result<(), Box<dyn Error>> {
     let path_root = PathBuf::from(get_rootpath_handler(&h)?);
     let path_sym = PATH_PARTY.join(format!("gamesyms/{}", h.uid));
-    if path_sym.exists() {
-        return Ok(());
+    let path_manifest = path_symlink_manifest(&h.uid);
+
+    let manifest: Option<SymlinkManifest> = File::open(&path_manifest)
+        .ok()
+        .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok());
+    let files = scan_game_tree(&path_root)?;
+
+    // Files the game wrote into copy_instead_paths live only here, so an existing folder is
+    // never thrown away
+    match manifest {
+        _ if !path_sym.exists() => build_symlink_folder(h, &path_root, &path_sym)?,
+        Some(manifest) if manifest.handler_version == h.version => {
+            sync_symlink_folder(h, &path_root, &path_sym, &manifest.files, &files)?;
+        }
+        Some(_) => {
+            println!("Handler for {} changed, rebuilding its symlinks", h.uid);
+            prune_symlink_folder(h, &path_sym)?;
+            let old = seed_manifest(&path_root, &path_sym, &files);
+            sync_symlink_folder(h, &path_root, &path_sym, &old, &files)?;
+            apply_handler_files(h, &path_root, &path_sym)?;
+        }
+        // Folders made before manifests were kept
+        None => {
+            let old = seed_manifest(&path_root, &path_sym, &files);
+            sync_symlink_folder(h, &path_root, &path_sym, &old, &files)?;
+        }
+    }
+
+    if let Some(parent) = path_manifest.parent() {
+        std::fs::create_dir_all(parent)?;
     }
+    let manifest = SymlinkManifest {
+        handler_version: h.version.clone(),
+        files,
+    };
+    serde_json::to_writer(File::create(&path_manifest)?, &manifest)?;
+
+    Ok(())
+}
+
+fn build_symlink_folder(
+    h: &Handler,
+    path_root: &PathBuf,
+    path_sym: &PathBuf,
+) -> Result<(), Box<dyn Error>> {
     std::fs::create_dir_all(path_sym.to_owned())?;
     copy_dir_recursive(&path_root, &path_sym, true, false)?;
 
@@ -306,15 +783,171 @@ pub fn create_symlink_folder(h: &Handler) -> Result<(), Box<dyn Error>> {
             std::fs::remove_file(p)?;
         }
     }
+
+    apply_handler_files(h, path_root, path_sym)
+}
+
+// Removes everything from a symlink folder except the real files under copy_instead_paths, which
+// may hold what the game wrote there
+fn prune_symlink_folder(h: &Handler, path_sym: &PathBuf) -> Result<(), Box<dyn Error>> {
+    for entry in walkdir::WalkDir::new(path_sym)
+        .min_depth(1)
+        .contents_first(true)
+        .follow_links(false)
+    {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(path_sym)?.to_string_lossy();
+        if entry.file_type().is_dir() {
+            // Fails for folders that still have kept files in them
+            let _ = std::fs::remove_dir(entry.path());
+        } else if entry.file_type().is_symlink() || !is_under(&rel, &h.copy_instead_paths) {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+// Works out a manifest for a symlink folder that doesn't have one, from the entries it already
+// has: those matching the game root count as up to date, and links to game files that are gone
+// count as removed, so syncing only fills in what's missing
+fn seed_manifest(
+    path_root: &PathBuf,
+    path_sym: &PathBuf,
+    files: &BTreeMap<String, ManifestEntry>,
+) -> BTreeMap<String, ManifestEntry> {
+    let mut out = BTreeMap::new();
+    for entry in walkdir::WalkDir::new(path_sym)
+        .min_depth(1)
+        .follow_links(false)
+        .into_iter()
+        .flatten()
+    {
+        let Ok(rel) = entry.path().strip_prefix(path_sym) else {
+            continue;
+        };
+        let rel = rel.to_string_lossy().to_string();
+        if let Some(known) = files.get(&rel) {
+            out.insert(rel, *known);
+        } else if entry.file_type().is_symlink()
+            && std::fs::read_link(entry.path()).is_ok_and(|t| t.starts_with(path_root))
+        {
+            out.insert(rel, ManifestEntry::default());
+        }
+    }
+    out
+}
+
+// Brings an existing symlink folder in line with the game root after a game update, only
+// touching the entries that were added, removed or changed since the manifest was written
+fn sync_symlink_folder(
+    h: &Handler,
+    path_root: &PathBuf,
+    path_sym: &PathBuf,
+    old: &BTreeMap<String, ManifestEntry>,
+    new: &BTreeMap<String, ManifestEntry>,
+) -> Result<(), Box<dyn Error>> {
+    let mut steam_api_changed = false;
+    let mut changes = 0;
+
+    let skipped = |rel: &str| is_under(rel, &h.remove_paths) || is_under(rel, &h.game_unique_paths);
+
+    for (rel, _) in old
+        .iter()
+        .filter(|(rel, entry)| !entry.dir && !new.contains_key(*rel))
+    {
+        steam_api_changed |= is_steam_api(h, rel);
+        let dest = path_sym.join(rel);
+        // Leave alone anything the handler put there
+        let ours = match dest.symlink_metadata() {
+            Ok(meta) if meta.is_symlink() => std::fs::read_link(&dest)?.starts_with(path_root),
+            Ok(_) => is_under(rel, &h.copy_instead_paths),
+            Err(_) => false,
+        };
+        if ours {
+            std::fs::remove_file(&dest)?;
+            changes += 1;
+        }
+    }
+
+    // Children come after their parents in the map, so going backwards empties them first
+    for (rel, _) in old
+        .iter()
+        .rev()
+        .filter(|(rel, entry)| entry.dir && !new.contains_key(*rel))
+    {
+        let dest = path_sym.join(rel);
+        if dest.symlink_metadata().is_ok_and(|meta| meta.is_dir())
+            && std::fs::remove_dir(&dest).is_ok()
+        {
+            changes += 1;
+        }
+    }
+
+    for (rel, entry) in new {
+        let is_new = !old.contains_key(rel);
+        if !is_new && old.get(rel) == Some(entry) {
+            continue;
+        }
+        if entry.dir {
+            if !skipped(rel) {
+                std::fs::create_dir_all(path_sym.join(rel))?;
+            }
+            continue;
+        }
+        steam_api_changed |= is_steam_api(h, rel);
+        if skipped(rel) {
+            continue;
+        }
+
+        let src = path_root.join(rel);
+        let dest = path_sym.join(rel);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        if is_under(rel, &h.copy_instead_paths) {
+            if dest.symlink_metadata().is_ok() {
+                std::fs::remove_file(&dest)?;
+            }
+            std::fs::copy(&src, &dest)?;
+            changes += 1;
+        } else if is_new && dest.symlink_metadata().is_err() {
+            let target = match src.symlink_metadata()?.is_symlink() {
+                true => std::fs::read_link(&src)?,
+                false => src,
+            };
+            std::os::unix::fs::symlink(target, &dest)?;
+            changes += 1;
+        }
+        // Changed files that are symlinked already point at the new version
+    }
+
+    if changes > 0 {
+        println!("Updated {changes} entries in symlink folder for {}", h.uid);
+    }
+    if steam_api_changed {
+        println!("Steam API changed for {}, setting up Goldberg again", h.uid);
+        apply_handler_files(h, path_root, path_sym)?;
+    }
+
+    Ok(())
+}
+
+// Puts the handler's copy_to_symdir files and Goldberg into dest, which is laid over the game
+// root: either the symlink folder itself, or the patch layer of an overlay
+pub fn apply_handler_files(
+    h: &Handler,
+    path_root: &Path,
+    dest_root: &PathBuf,
+) -> Result<(), Box<dyn Error>> {
     let copypath = PathBuf::from(&h.path_handler).join("copy_to_symdir");
     if copypath.exists() {
-        copy_dir_recursive(&copypath, &path_sym, false, true)?;
+        copy_dir_recursive(&copypath, dest_root, false, true)?;
     }
 
     // Insert goldberg dll
     if !h.path_goldberg.is_empty() {
-        let dest = path_sym.join(&h.path_goldberg);
-
+        let dest = dest_root.join(&h.path_goldberg);
         let steam_settings = dest.join("steam_settings");
         if !steam_settings.exists() {
             std::fs::create_dir_all(steam_settings.clone())?;
@@ -326,14 +959,15 @@ pub fn create_symlink_folder(h: &Handler) -> Result<(), Box<dyn Error>> {
         if let Some(appid) = &h.steam_appid {
             std::fs::write(steam_settings.join("steam_appid.txt"), appid.as_str())?;
         }
+        write_goldberg_configs(h, &steam_settings)?;
 
         // If the game uses goldberg coldclient, assume the handler owner has set up coldclient in the copy_to_symdir files
         // And so we don't copy goldberg dlls or generate interfaces
         if !&h.coldclient {
-            let mut src = PATH_RES.clone();
+            let mut src = PATH_GOLDBERG.clone();
             src = match &h.win {
-                true => src.join("goldberg/win"),
-                false => src.join("goldberg/linux"),
+                true => src.join("win"),
+                false => src.join("linux"),
             };
             src = match &h.is32bit {
                 true => src.join("x32"),
@@ -351,19 +985,71 @@ pub fn create_symlink_folder(h: &Handler) -> Result<(), Box<dyn Error>> {
                 false => path_steamdll.join("libsteam_api.so"),
             };
 
-            let gen_interfaces = match &h.is32bit {
-                true => PATH_RES.join("goldberg/generate_interfaces_x32"),
-                false => PATH_RES.join("goldberg/generate_interfaces_x64"),
-            };
-            let status = std::process::Command::new(gen_interfaces)
-                .arg(steamdll)
-                .current_dir(steam_settings)
-                .status()?;
-            if !status.success() {
-                return Err("Generate interfaces failed".into());
-            }
+            generate_interfaces(h, &steamdll, &steam_settings)?;
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_handler_json_rules() {
+        let mut base = json!({
+            "game.args": ["-windowed"],
+            "profiles.game_paths": ["saves"],
+            "game.exec": "base.exe",
+            "nested": {"keep": 1, "list": [1]},
+        });
+        let child = json!({
+            "game.args": ["-nosound"],
+            "profiles.game_paths": ["config"],
+            "game.exec": "child.exe",
+            "nested": {"list": [2], "added": true},
+        });
+        let replace = ["profiles.game_paths".to_string()];
+        merge_handler_json(&mut base, &child, &replace, "");
+        assert_eq!(
+            base,
+            json!({
+                "game.args": ["-windowed", "-nosound"],
+                "profiles.game_paths": ["config"],
+                "game.exec": "child.exe",
+                "nested": {"keep": 1, "list": [1, 2], "added": true},
+            })
+        );
+    }
+
+    #[test]
+    fn tag_handler_dirs_follow_overrides() {
+        let mut base = json!({
+            "goldberg.achievements": "achievements.json",
+            "goldberg.stats": "stats.txt",
+            "profiles.files": [{"path": "a.cfg", "type": "text", "template": "a.cfg"}],
+        });
+        tag_handler_dirs(&mut base, Path::new("/templates"));
+        let mut child = json!({
+            "goldberg.stats": "mystats.txt",
+            "goldberg.achievements.dir": "/etc",
+            "profiles.files": [{"path": "b.cfg", "type": "text", "template": "b.cfg"}],
+        });
+        tag_handler_dirs(&mut child, Path::new("/handlers/game"));
+        merge_handler_json(&mut base, &child, &[], "");
+        assert_eq!(base["goldberg.achievements.dir"], "/templates");
+        assert_eq!(base["goldberg.stats.dir"], "/handlers/game");
+        assert_eq!(base["profiles.files"][0]["dir"], "/templates");
+        assert_eq!(base["profiles.files"][1]["dir"], "/handlers/game");
+    }
+
+    #[test]
+    fn merge_handler_json_replace_nested() {
+        let mut base = json!({"nested": {"list": [1]}});
+        let replace = ["nested.list".to_string()];
+        merge_handler_json(&mut base, &json!({"nested": {"list": [2]}}), &replace, "");
+        assert_eq!(base, json!({"nested": {"list": [2]}}));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::handler::Handler;
+use crate::util::SanitizePath;
+
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{AST, Dynamic, Engine, EvalAltResult, Map, Scope};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+
+// Hook functions a handler script can define
+// Runs once, when a profile's save for the game is first created
+pub const HOOK_GAMESAVE: &str = "on_gamesave";
+pub const HOOK_INSTANCE: &str = "on_instance";
+pub const HOOK_SESSION_END: &str = "on_session_end";
+
+thread_local! {
+    // Compiled hook scripts by path. Each launch runs on its own thread, so scripts are compiled
+    // once per launch and edits are picked up by the next one.
+    static SCRIPTS: RefCell<HashMap<PathBuf, AST>> = RefCell::new(HashMap::new());
+}
+
+pub struct HookContext {
+    pub profile: String,
+    pub instance: usize,
+    pub width: u32,
+    pub height: u32,
+    pub path_profile: PathBuf,
+    pub path_save: PathBuf,
+}
+
+impl HookContext {
+    fn to_map(&self) -> Map {
+        let mut map = Map::new();
+        map.insert("profile".into(), self.profile.clone().into());
+        map.insert("instance".into(), (self.instance as i64).into());
+        map.insert("width".into(), (self.width as i64).into());
+        map.insert("height".into(), (self.height as i64).into());
+        map.insert(
+            "save_path".into(),
+            self.path_save.to_string_lossy().to_string().into(),
+        );
+        map.insert(
+            "profile_path".into(),
+            self.path_profile.to_string_lossy().to_string().into(),
+        );
+        map
+    }
+}
+
+// Resolves a script-provided path inside one of the allowed roots ("save" or "profile").
+// Scripts never get to touch anything outside of these two directories.
+fn sandbox_path(
+    roots: &[(&'static str, PathBuf)],
+    root: &str,
+    path: &str,
+) -> Result<PathBuf, Box<EvalAltResult>> {
+    let (_, base) = roots
+        .iter()
+        .find(|(name, _)| *name == root)
+        .ok_or_else(|| format!("Unknown root \"{root}\", expected \"save\" or \"profile\""))?;
+
+    let full = base.join(path.to_string().sanitize_path());
+
+    // Symlinks inside the save could still point elsewhere. Paths that don't exist yet end up
+    // wherever the nearest folder that does exist resolves to.
+    let canon_base = base
+        .canonicalize()
+        .map_err(|e| format!("Couldn't resolve the {root} directory: {e}"))?;
+    let mut existing = full.as_path();
+    let canon_full = loop {
+        match existing.canonicalize() {
+            Ok(canon) => break canon,
+            // A dangling symlink would be followed when writing through it
+            Err(_) if existing.symlink_metadata().is_ok() => {
+                return Err(format!("Path \"{path}\" can't be resolved").into());
+            }
+            Err(_) => {
+                existing = existing
+                    .parent()
+                    .ok_or_else(|| format!("Path \"{path}\" can't be resolved"))?;
+            }
+        }
+    };
+    if !canon_full.starts_with(&canon_base) {
+        return Err(format!("Path \"{path}\" escapes the {root} directory").into());
+    }
+    Ok(full)
+}
+
+fn build_engine(ctx: &HookContext) -> Engine {
+    let mut engine = Engine::new();
+    // The default resolver would let a script import any .rhai file on disk
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.set_max_operations(10_000_000);
+    engine.set_max_call_levels(32);
+
+    let roots = vec![
+        ("save", ctx.path_save.clone()),
+        ("profile", ctx.path_profile.clone()),
+    ];
+
+    let r = roots.clone();
+    engine.register_fn(
+        "read_file",
+        move |root: &str, path: &str| -> Result<String, Box<EvalAltResult>> {
+            let p = sandbox_path(&r, root, path)?;
+            std::fs::read_to_string(&p)
+                .map_err(|e| format!("Couldn't read {}: {e}", p.display()).into())
+        },
+    );
+
+    let r = roots.clone();
+    engine.register_fn(
+        "write_file",
+        move |root: &str, path: &str, contents: &str| -> Result<(), Box<EvalAltResult>> {
+            let p = sandbox_path(&r, root, path)?;
+            if let Some(parent) = p.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Couldn't create {}: {e}", parent.display()))?;
+            }
+            std::fs::write(&p, contents)
+                .map_err(|e| format!("Couldn't write {}: {e}", p.display()).into())
+        },
+    );
+
+    let r = roots.clone();
+    engine.register_fn(
+        "file_exists",
+        move |root: &str, path: &str| -> Result<bool, Box<EvalAltResult>> {
+            Ok(sandbox_path(&r, root, path)?.exists())
+        },
+    );
+
+    let r = roots.clone();
+    engine.register_fn(
+        "create_dir",
+        move |root: &str, path: &str| -> Result<(), Box<EvalAltResult>> {
+            let p = sandbox_path(&r, root, path)?;
+            std::fs::create_dir_all(&p)
+                .map_err(|e| format!("Couldn't create {}: {e}", p.display()).into())
+        },
+    );
+
+    engine.on_print(|s| println!("[HOOK] {s}"));
+
+    engine
+}
+
+// Runs the given hook function from the handler's script, if the handler has one
+// and the script defines it.
+pub fn run_hook(h: &Handler, hook: &str, ctx: &HookContext) -> Result<(), Box<dyn Error>> {
+    if h.hooks.is_empty() {
+        return Ok(());
+    }
+
+    let path_script = h.path_handler.join(&h.hooks);
+    if !path_script.exists() {
+        return Err(format!("Hook script not found: {}", path_script.display()).into());
+    }
+
+    let engine = build_engine(ctx);
+    let ast = match SCRIPTS.with_borrow(|scripts| scripts.get(&path_script).cloned()) {
+        Some(ast) => ast,
+        None => {
+            let ast = engine.compile_file(path_script.clone())?;
+            SCRIPTS.with_borrow_mut(|scripts| scripts.insert(path_script, ast.clone()));
+            ast
+        }
+    };
+
+    if !ast.iter_functions().any(|f| f.name == hook) {
+        return Ok(());
+    }
+
+    println!(
+        "Running {hook} hook for {} (instance {})",
+        h.uid,
+        ctx.instance + 1
+    );
+    let mut scope = Scope::new();
+    // Whatever the hook returns is ignored
+    let _ = engine
+        .call_fn::<Dynamic>(&mut scope, &ast, hook, (ctx.to_map(),))
+        .map_err(|e| format!("{hook} hook failed: {e}"))?;
+
+    Ok(())
+}
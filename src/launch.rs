@@ -3,9 +3,16 @@ use std::path::PathBuf;
 use crate::app::PartyConfig;
 use crate::game::Game;
 use crate::handler::*;
+use crate::hooks::*;
 use crate::input::*;
 use crate::launch::Game::{ExecRef, HandlerRef};
+use crate::overlay::*;
 use crate::paths::*;
+use crate::prefix::*;
+use crate::proton::resolve_proton;
+use crate::runtime::RuntimeRegistry;
+use crate::snapshots::{SnapshotKind, create_snapshot, prune_snapshots};
+use crate::templates::*;
 use crate::util::*;
 
 pub fn launch_game(
@@ -14,13 +21,59 @@ pub fn launch_game(
     instances: &Vec<Instance>,
     cfg: &PartyConfig,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    // Catch missing dependencies here, rather than partway through setting up the game
+    let win = match game {
+        ExecRef(e) => e.path().extension().unwrap_or_default() == "exe",
+        HandlerRef(h) => h.win,
+    };
+    if win && !BIN_UMU_RUN.exists() {
+        return Err(
+            "UMU Launcher is not installed. Install it from Settings > Dependencies.".into(),
+        );
+    }
     if let HandlerRef(h) = game {
-        for instance in instances {
+        if !h.path_goldberg.is_empty() && !h.coldclient && !PATH_GOLDBERG.exists() {
+            return Err(
+                "Goldberg Steam Emu is not installed. Install it from Settings > Dependencies."
+                    .into(),
+            );
+        }
+        for (i, instance) in instances.iter().enumerate() {
             create_profile(instance.profname.as_str())?;
-            create_gamesave(instance.profname.as_str(), &h)?;
+            let new_save = create_gamesave(instance.profname.as_str(), h)?;
+            // Guests are thrown away sooner or later, so there's nothing to protect
+            if cfg.snapshot_keep > 0 && !instance.profname.starts_with('.') {
+                let keep = cfg.snapshot_keep as usize;
+                if let Err(err) = create_snapshot(&instance.profname, h, "", SnapshotKind::Launch)
+                    .and_then(|_| prune_snapshots(&instance.profname, &h.uid, keep))
+                {
+                    println!("Couldn't take snapshot of {}: {err}", instance.profname);
+                }
+            }
+            if new_save {
+                run_hook(h, HOOK_GAMESAVE, &hook_context(h, instance, i, 0, 0))?;
+            }
         }
         if h.symlink_dir {
-            create_symlink_folder(&h)?;
+            match use_overlay(h) {
+                true => prepare_overlay(h, instances.len())?,
+                false => create_symlink_folder(h)?,
+            }
+        }
+        // Profile data gets bound over this, so it has to exist
+        if !h.flatpak.is_empty() {
+            std::fs::create_dir_all(PATH_HOME.join(".var/app").join(&h.flatpak))?;
+        }
+    }
+
+    if win {
+        let protonpath = resolve_proton(&cfg.proton_version)?;
+        for instance in instances {
+            let path_pfx = instance_prefix(game, cfg, &instance.profname);
+            prepare_prefix(&path_pfx)?;
+            if let HandlerRef(h) = game {
+                apply_wine_verbs(h, &path_pfx, &protonpath)?;
+            }
         }
     }
 
@@ -46,11 +99,53 @@ pub fn launch_game(
         kwin_dbus_unload_script()?;
     }
 
-    remove_guest_profiles()?;
+    if let HandlerRef(h) = game {
+        if h.symlink_dir && use_overlay(h) {
+            cleanup_overlay(h, instances.len());
+        }
+        for (i, instance) in instances.iter().enumerate() {
+            if let Err(err) = run_hook(h, HOOK_SESSION_END, &hook_context(h, instance, i, 0, 0)) {
+                println!("{err}");
+            }
+        }
+    }
+
+    if !cfg.keep_guests {
+        offer_guest_promotions();
+        remove_guest_profiles()?;
+    }
 
     Ok(())
 }
 
+// Double quotes a value for the launch command, escaping what the shell would still expand
+fn shell_quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+        .replace('`', "\\`");
+    format!("\"{escaped}\"")
+}
+
+fn hook_context(
+    h: &Handler,
+    instance: &Instance,
+    i: usize,
+    width: u32,
+    height: u32,
+) -> HookContext {
+    let path_profile = PATH_PARTY.join("profiles").join(&instance.profname);
+    HookContext {
+        profile: instance.profname.clone(),
+        instance: i,
+        width,
+        height,
+        path_save: path_profile.join("saves").join(&h.uid),
+        path_profile,
+    }
+}
+
 pub fn launch_cmd(
     game: &Game,
     input_devices: &[DeviceInfo],
@@ -72,6 +167,11 @@ pub fn launch_cmd(
                 .ok_or_else(|| "Invalid path")?
                 .to_string_lossy()
         ),
+        // Flatpaks don't have a game folder of their own
+        HandlerRef(h) if !h.flatpak.is_empty() => &format!("{home}"),
+        HandlerRef(h) if h.symlink_dir && use_overlay(h) => {
+            &format!("{}", path_overlay_mount(h).display())
+        }
         HandlerRef(h) => match h.symlink_dir {
             true => &format!("{party}/gamesyms/{}", h.uid),
             false => &get_rootpath_handler(&h)?,
@@ -100,64 +200,89 @@ pub fn launch_cmd(
         cmd.push_str(&format!("SDL_DYNAMIC_API=\"{steam}/{path_sdl}\" "));
     }
     if win {
-        let protonpath = match cfg.proton_version.is_empty() {
-            true => "GE-Proton",
-            false => cfg.proton_version.as_str(),
-        };
-        cmd.push_str(&format!("PROTON_VERB=run WINEPREFIX={party}/pfx "));
-        cmd.push_str(&format!("PROTONPATH={protonpath} "));
+        let protonpath = resolve_proton(&cfg.proton_version)?;
+        cmd.push_str("PROTON_VERB=run ");
+        cmd.push_str(&format!("PROTONPATH=\"{protonpath}\" "));
 
         if let HandlerRef(h) = game {
-            if !h.dll_overrides.is_empty() {
-                cmd.push_str("WINEDLLOVERRIDES=\"");
-                for dll in &h.dll_overrides {
-                    cmd.push_str(&format!("{dll},"));
-                }
-                cmd.push_str("=n,b\" ");
+            let overrides = dll_overrides(h);
+            if !overrides.is_empty() {
+                cmd.push_str(&format!("WINEDLLOVERRIDES=\"{overrides}\" "));
             }
             if h.coldclient {
                 cmd.push_str("PROTON_DISABLE_LSTEAMCLIENT=1 ");
             }
         }
     }
+
+    let exec = match game {
+        ExecRef(e) => e.filename(),
+        HandlerRef(h) => h.exec.as_str(),
+    };
+
+    // AppImages are extracted to /tmp instead of mounted, since FUSE mounts don't play
+    // well with bwrap. Each instance gets its own /tmp so they don't collide.
+    if is_appimage(exec) {
+        cmd.push_str("APPIMAGE_EXTRACT_AND_RUN=1 ");
+    }
+
+    // Handler env first, so user overrides win when the same variable is set twice
+    let mut env: Vec<(String, String)> = match game {
+        HandlerRef(h) => h.env.clone(),
+        ExecRef(_) => Vec::new(),
+    };
+    for var in cfg
+        .extra_env_vars
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+    {
+        match var.split_once('=') {
+            Some((k, v)) => env.push((k.to_string(), v.to_string())),
+            None => println!("Ignoring invalid environment variable: {var}"),
+        }
+    }
+    for (k, v) in &env {
+        match env_assignment(k, v) {
+            Some(assignment) => cmd.push_str(&format!("{assignment} ")),
+            None => println!("Ignoring invalid environment variable name: {k}"),
+        }
+    }
     cmd.push_str("; ");
 
     let runtime = match win {
         // UMU CHANGE
-        true => &format!("{}", BIN_UMU_RUN.to_string_lossy()),
-        false => {
-            if let HandlerRef(h) = game {
-                match h.runtime.as_str() {
-                    "scout" => &format!("\"{steam}/ubuntu12_32/steam-runtime/run.sh\""),
-                    "soldier" => &format!(
-                        "\"{steam}/steamapps/common/SteamLinuxRuntime_soldier/_v2-entry-point\""
-                    ),
-                    _ => "",
+        true => format!("{}", BIN_UMU_RUN.to_string_lossy()),
+        false => match game {
+            HandlerRef(h) => {
+                let registry = RuntimeRegistry::load();
+                let (runtime, warning) = registry.resolve_or_host(&h.runtime)?;
+                if let Some(warning) = warning {
+                    println!("{warning}");
+                    msg("Runtime not found", &warning);
                 }
-            } else {
-                ""
+                runtime.command()
             }
-        }
-    };
-
-    let exec = match game {
-        ExecRef(e) => &e.filename(),
-        HandlerRef(h) => h.exec.as_str(),
+            ExecRef(_) => String::new(),
+        },
     };
 
-    if !PathBuf::from(gamedir).join(exec).exists() {
-        return Err(format!("Executable not found: {gamedir}/{exec}").into());
-    }
-
-    if let HandlerRef(h) = game {
-        if h.runtime == "scout" && !PATH_STEAM.join("ubuntu12_32/steam-runtime/run.sh").exists() {
-            return Err("Steam Scout Runtime not found".into());
-        } else if h.runtime == "soldier"
-            && !PATH_STEAM
-                .join("steamapps/common/SteamLinuxRuntime_soldier")
-                .exists()
-        {
-            return Err("Steam Soldier Runtime not found".into());
+    match game {
+        HandlerRef(h) if !h.flatpak.is_empty() => {
+            if !flatpak_installed(&h.flatpak) {
+                return Err(format!("Flatpak app not installed: {}", h.flatpak).into());
+            }
+        }
+        // The overlay is only mounted inside the sandbox, so look at its layers instead
+        HandlerRef(h) if h.symlink_dir && use_overlay(h) => {
+            if !overlay_has_file(h, exec)? {
+                return Err(format!("Executable not found: {exec}").into());
+            }
+        }
+        _ => {
+            if !PathBuf::from(gamedir).join(exec).exists() {
+                return Err(format!("Executable not found: {gamedir}/{exec}").into());
+            }
         }
     }
 
@@ -186,6 +311,33 @@ pub fn launch_cmd(
             gsc_lowres_warn = false;
         }
 
+        let vars = InstanceVars {
+            gamedir: gamedir.to_string(),
+            profile: instance.profname.clone(),
+            instance: i,
+            width: gsc_width,
+            height: gsc_height,
+            port_base: match game {
+                HandlerRef(h) => h.port_base,
+                ExecRef(_) => 0,
+            },
+        };
+
+        if let HandlerRef(h) = game {
+            apply_profile_files(h, &vars, &PathBuf::from(path_save))?;
+            run_hook(
+                h,
+                HOOK_INSTANCE,
+                &hook_context(h, instance, i, gsc_width, gsc_height),
+            )?;
+        }
+
+        // Each instance can have its own prefix, so WINEPREFIX is set per gamescope command
+        let path_pfx = instance_prefix(game, cfg, &instance.profname);
+        if win {
+            cmd.push_str(&format!("WINEPREFIX=\"{}\" ", path_pfx.display()));
+        }
+
         let gsc_sdl = match cfg.gamescope_sdl_backend {
             true => "--backend=sdl",
             false => "",
@@ -200,6 +352,22 @@ pub fn launch_cmd(
             "{gamescope} -W {gsc_width} -H {gsc_height} {gsc_sdl} "
         ));
 
+        if let HandlerRef(h) = game {
+            if h.gamescope_nested_width > 0 && h.gamescope_nested_height > 0 {
+                cmd.push_str(&format!(
+                    "-w {} -h {} ",
+                    h.gamescope_nested_width, h.gamescope_nested_height
+                ));
+            }
+            if h.gamescope_fsr {
+                cmd.push_str("-F fsr ");
+            }
+            if h.gamescope_fps_limit > 0 {
+                cmd.push_str(&format!("-r {} ", h.gamescope_fps_limit));
+            }
+        }
+        cmd.push_str(&gamescope_extra_args(game, cfg));
+
         if cfg.kbm_support {
             let mut instance_has_keyboard = false;
             let mut instance_has_mouse = false;
@@ -234,6 +402,13 @@ pub fn launch_cmd(
         cmd.push_str(&format!(
             "bwrap --die-with-parent --dev-bind / / --tmpfs /tmp "
         ));
+        if let HandlerRef(h) = game
+            && h.symlink_dir
+            && use_overlay(h)
+        {
+            cmd.push_str(&overlay_binds(h, i)?);
+            cmd.push_str(&format!("--chdir \"{gamedir}\" "));
+        }
 
         // Bind player profile directories to the game's directories
         let mut binds = String::new();
@@ -256,7 +431,7 @@ pub fn launch_cmd(
                 ));
             }
             if h.win {
-                let path_windata = format!("{party}/pfx/drive_c/users/steamuser/");
+                let path_windata = format!("{}/drive_c/users/steamuser", path_pfx.display());
                 if h.win_unique_appdata {
                     binds.push_str(&format!(
                         "--bind \"{path_save}/_AppData\" \"{path_windata}/AppData\" "
@@ -277,6 +452,12 @@ pub fn launch_cmd(
                     ));
                 }
             }
+            if !h.flatpak.is_empty() {
+                binds.push_str(&format!(
+                    "--bind \"{path_save}/_flatpak\" \"{home}/.var/app/{}\" ",
+                    h.flatpak
+                ));
+            }
             for subdir in &h.game_unique_paths {
                 binds.push_str(&format!(
                     "--bind \"{path_save}/{subdir}\" \"{gamedir}/{subdir}\" "
@@ -294,13 +475,20 @@ pub fn launch_cmd(
                     "$WIDTH" => format!(" {gsc_width}"),
                     "$HEIGHT" => format!(" {gsc_height}"),
                     "$WIDTHXHEIGHT" => format!(" \"{gsc_width}x{gsc_height}\""),
+                    // Expanded paths can have spaces or anything else in them
+                    _ if arg.contains('$') => format!(" {}", shell_quote(&vars.expand(arg))),
                     _ => format!(" {arg}"),
                 })
                 .collect::<String>(),
             ExecRef(e) => e.args.clone().sanitize_path(),
         };
 
-        cmd.push_str(&format!("{binds} {runtime} \"{gamedir}/{exec}\" {args} "));
+        let target = match game {
+            HandlerRef(h) if !h.flatpak.is_empty() => flatpak_run(h),
+            _ => format!("{runtime} \"{gamedir}/{exec}\""),
+        };
+
+        cmd.push_str(&format!("{binds} {target} {args} "));
 
         if i < instances.len() - 1 {
             // Proton games need a ~5 second buffer in-between launches
@@ -314,3 +502,94 @@ pub fn launch_cmd(
 
     Ok(cmd)
 }
+
+fn is_appimage(exec: &str) -> bool {
+    exec.to_lowercase().ends_with(".appimage")
+}
+
+fn flatpak_installed(app_id: &str) -> bool {
+    std::process::Command::new("flatpak")
+        .arg("info")
+        .arg(app_id)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+// Flatpak sets up its own sandbox inside ours, so the masked input devices and the
+// profile's app data bound over ~/.var/app/<id> carry through to the game
+fn flatpak_run(h: &Handler) -> String {
+    let mut out = String::from("flatpak run --device=all ");
+    for fs in &h.flatpak_filesystems {
+        out.push_str(&format!("--filesystem=\"{fs}\" "));
+    }
+    for persist in &h.flatpak_persist {
+        out.push_str(&format!("--persist=\"{persist}\" "));
+    }
+    out.push_str(&h.flatpak);
+    out
+}
+
+// game.dll_overrides are always native then builtin, wine.dll_overrides pick the mode per DLL
+fn dll_overrides(h: &Handler) -> String {
+    h.dll_overrides
+        .iter()
+        .map(|dll| (dll.as_str(), "n,b"))
+        .chain(
+            h.dll_modes
+                .iter()
+                .map(|(dll, mode)| (dll.as_str(), mode.as_str())),
+        )
+        .filter(|(dll, _)| {
+            !dll.is_empty()
+                && dll
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c))
+        })
+        .map(|(dll, mode)| format!("{dll}={mode}"))
+        .collect::<Vec<String>>()
+        .join(";")
+}
+
+// Builds a shell-safe VAR="value" pair, or None if the name isn't a valid variable name
+fn env_assignment(key: &str, value: &str) -> Option<String> {
+    let mut chars = key.chars();
+    let first = chars.next()?;
+    if !(first.is_ascii_alphabetic() || first == '_')
+        || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+    Some(format!("{key}={}", shell_quote(value)))
+}
+
+// Handler-provided gamescope args followed by the user's, with anything that could
+// break out of the shell command dropped
+fn gamescope_extra_args(game: &Game, cfg: &PartyConfig) -> String {
+    let handler_args = match game {
+        HandlerRef(h) => h.gamescope_args.clone(),
+        ExecRef(_) => Vec::new(),
+    };
+    let user_args = cfg
+        .extra_gamescope_args
+        .split_whitespace()
+        .map(|a| a.to_string());
+
+    let mut out = String::new();
+    for arg in handler_args.into_iter().chain(user_args) {
+        if arg.is_empty() {
+            continue;
+        }
+        if !arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=.,:/".contains(c))
+        {
+            println!("Ignoring unsafe gamescope argument: {arg}");
+            continue;
+        }
+        out.push_str(&format!("{arg} "));
+    }
+    out
+}
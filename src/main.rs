@@ -1,9 +1,23 @@
 mod app;
+mod deps;
+mod doctor;
 mod game;
+mod goldberg;
 mod handler;
+mod hooks;
 mod input;
 mod launch;
+mod overlay;
 mod paths;
+mod prefix;
+mod proton;
+mod runtime;
+mod savesync;
+mod snapshots;
+mod storage;
+mod templates;
+mod transfer;
+mod trust;
 mod util;
 
 use crate::app::*;
@@ -18,6 +32,14 @@ fn main() -> eframe::Result {
         std::process::exit(0);
     }
 
+    if args.get(1).is_some_and(|arg| arg == "doctor") {
+        let checks = crate::doctor::run_checks(&load_cfg());
+        match crate::doctor::print_checks(&checks) {
+            true => std::process::exit(0),
+            false => std::process::exit(1),
+        }
+    }
+
     if std::env::args().any(|arg| arg == "--kwin") {
         let args: Vec<String> = std::env::args().filter(|arg| arg != "--kwin").collect();
 
@@ -76,8 +98,13 @@ fn main() -> eframe::Result {
     std::fs::create_dir_all(PATH_PARTY.join("profiles"))
         .expect("Failed to create profiles directory");
 
+    // Guests can be left over from a session that didn't end in the GUI, so their progress is
+    // offered to keep here too
+    offer_guest_promotions();
     remove_guest_profiles().unwrap();
+    crate::goldberg::migrate_profile_steam_ids();
 
+    crate::overlay::cleanup_stale_overlays();
     if PATH_PARTY.join("tmp").exists() {
         std::fs::remove_dir_all(PATH_PARTY.join("tmp")).unwrap();
     }
@@ -110,7 +137,7 @@ fn main() -> eframe::Result {
 
     println!("\n[PARTYDECK] starting...\n");
 
-    eframe::run_native(
+    let result = eframe::run_native(
         "PartyDeck",
         options,
         Box::new(|cc| {
@@ -122,12 +149,19 @@ fn main() -> eframe::Result {
                 false => Box::<PartyApp>::default(),
             })
         }),
-    )
+    );
+
+    // Guests kept between sessions are only removed once the app is closed
+    offer_guest_promotions();
+    remove_guest_profiles().unwrap();
+
+    result
 }
 
 static USAGE_TEXT: &str = r#"
 {}
 Usage: partydeck [OPTIONS]
+       partydeck doctor      Check that everything needed to launch games is set up, and suggest fixes for what isn't
 
 Options:
     --exec <executable>   Execute the specified executable in splitscreen. If this isn't specified, PartyDeck will launch in the regular GUI mode.
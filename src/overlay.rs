@@ -0,0 +1,207 @@
+use crate::handler::*;
+use crate::paths::*;
+use crate::util::get_rootpath_handler;
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+// How an overlay game folder is put together. bwrap can mount overlays itself since 0.10,
+// otherwise each instance gets a fuse-overlayfs mount that's bound into its sandbox.
+#[derive(Clone, Copy, PartialEq)]
+pub enum OverlayBackend {
+    Bwrap,
+    Fuse,
+}
+
+static OVERLAY_BACKEND: LazyLock<Option<OverlayBackend>> = LazyLock::new(|| {
+    if let Ok(output) = std::process::Command::new("bwrap").arg("--help").output()
+        && String::from_utf8_lossy(&output.stdout).contains("--overlay-src")
+    {
+        println!("Using bwrap overlays for game folders");
+        return Some(OverlayBackend::Bwrap);
+    }
+    if std::process::Command::new("fuse-overlayfs")
+        .arg("--version")
+        .output()
+        .is_ok()
+    {
+        println!("Using fuse-overlayfs for game folders");
+        return Some(OverlayBackend::Fuse);
+    }
+    println!("No overlay support found, falling back to symlinked game folders");
+    None
+});
+
+pub fn overlay_backend() -> Option<OverlayBackend> {
+    *OVERLAY_BACKEND
+}
+
+// Overlays can't hide files from the game folder without whiteouts, which need root to create,
+// so handlers that remove paths keep using the symlink folder.
+pub fn use_overlay(h: &Handler) -> bool {
+    h.remove_paths.is_empty() && OVERLAY_BACKEND.is_some()
+}
+
+fn path_overlay(h: &Handler) -> PathBuf {
+    PATH_PARTY.join("gameoverlays").join(&h.uid)
+}
+
+// Where the merged game folder shows up inside every instance's sandbox
+pub fn path_overlay_mount(h: &Handler) -> PathBuf {
+    path_overlay(h).join("mnt")
+}
+
+fn path_overlay_patch(h: &Handler) -> PathBuf {
+    path_overlay(h).join("patch")
+}
+
+// Each instance keeps its own upper layer between sessions, so whatever the game writes
+// into its folder is still there next time
+fn path_overlay_instance(h: &Handler, i: usize) -> PathBuf {
+    path_overlay(h).join("instances").join(i.to_string())
+}
+
+// Handler files are rebuilt on every launch, so they win over copies the game made of them
+// in an earlier session
+fn remove_patched_from_upper(path_patch: &Path, upper: &Path) -> Result<(), Box<dyn Error>> {
+    for entry in walkdir::WalkDir::new(path_patch).into_iter().flatten() {
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let Ok(rel) = entry.path().strip_prefix(path_patch) else {
+            continue;
+        };
+        let path_upper = upper.join(rel);
+        if path_upper.is_file() || path_upper.is_symlink() {
+            std::fs::remove_file(path_upper)?;
+        }
+    }
+    Ok(())
+}
+
+// Checks the patch layer first, then the real game folder
+pub fn overlay_has_file(h: &Handler, rel: &str) -> Result<bool, Box<dyn Error>> {
+    let path_root = PathBuf::from(get_rootpath_handler(h)?);
+    Ok(path_overlay_patch(h).join(rel).exists() || path_root.join(rel).exists())
+}
+
+// Rebuilds the patch layer (handler files and Goldberg) that sits on top of the real game
+// folder. The game folder itself is never copied, so game updates show up on their own.
+pub fn prepare_overlay(h: &Handler, instances: usize) -> Result<(), Box<dyn Error>> {
+    let path_root = PathBuf::from(get_rootpath_handler(h)?);
+    let path_patch = path_overlay_patch(h);
+    if path_patch.exists() {
+        std::fs::remove_dir_all(&path_patch)?;
+    }
+    std::fs::create_dir_all(&path_patch)?;
+    std::fs::create_dir_all(path_overlay_mount(h))?;
+    apply_handler_files(h, &path_root, &path_patch)?;
+
+    for i in 0..instances {
+        let path_instance = path_overlay_instance(h, i);
+        let (upper, work, mnt) = (
+            path_instance.join("upper"),
+            path_instance.join("work"),
+            path_instance.join("mnt"),
+        );
+        if *OVERLAY_BACKEND == Some(OverlayBackend::Fuse) {
+            unmount_fuse(&mnt);
+        }
+        // The work dir is only scratch space for the overlay, unlike the upper dir
+        if work.exists() {
+            std::fs::remove_dir_all(&work)?;
+        }
+        for dir in [&upper, &work, &mnt] {
+            std::fs::create_dir_all(dir)?;
+        }
+        remove_patched_from_upper(&path_patch, &upper)?;
+
+        if *OVERLAY_BACKEND != Some(OverlayBackend::Fuse) {
+            continue;
+        }
+
+        // In fuse-overlayfs, the leftmost lowerdir is the top layer
+        let status = std::process::Command::new("fuse-overlayfs")
+            .arg("-o")
+            .arg(format!(
+                "lowerdir={}:{},upperdir={},workdir={}",
+                path_patch.display(),
+                path_root.display(),
+                upper.display(),
+                work.display()
+            ))
+            .arg(&mnt)
+            .status()?;
+        if !status.success() {
+            return Err(format!("fuse-overlayfs failed to mount {}", mnt.display()).into());
+        }
+    }
+
+    Ok(())
+}
+
+// bwrap arguments that mount the merged game folder for instance i. These need to come before
+// any binds that point into the game folder.
+pub fn overlay_binds(h: &Handler, i: usize) -> Result<String, Box<dyn Error>> {
+    let mnt = path_overlay_mount(h);
+    match *OVERLAY_BACKEND {
+        // bwrap stacks --overlay-src layers in order, so the real game folder goes first
+        Some(OverlayBackend::Bwrap) => Ok(format!(
+            "--overlay-src \"{}\" --overlay-src \"{}\" --overlay \"{}\" \"{}\" \"{}\" ",
+            get_rootpath_handler(h)?,
+            path_overlay_patch(h).display(),
+            path_overlay_instance(h, i).join("upper").display(),
+            path_overlay_instance(h, i).join("work").display(),
+            mnt.display()
+        )),
+        Some(OverlayBackend::Fuse) => Ok(format!(
+            "--bind \"{}\" \"{}\" ",
+            path_overlay_instance(h, i).join("mnt").display(),
+            mnt.display()
+        )),
+        None => Err("Overlays are not supported on this system".into()),
+    }
+}
+
+pub fn cleanup_overlay(h: &Handler, instances: usize) {
+    if *OVERLAY_BACKEND != Some(OverlayBackend::Fuse) {
+        return;
+    }
+    for i in 0..instances {
+        unmount_fuse(&path_overlay_instance(h, i).join("mnt"));
+    }
+}
+
+// Unmounts fuse-overlayfs mounts left behind by a session that didn't end cleanly
+pub fn cleanup_stale_overlays() {
+    let Ok(mounts) = std::fs::read_to_string("/proc/self/mounts") else {
+        return;
+    };
+    let path_overlays = PATH_PARTY.join("gameoverlays");
+    for line in mounts.lines() {
+        let Some(mnt) = line.split(' ').nth(1) else {
+            continue;
+        };
+        // Spaces in mount points are escaped as \040
+        let mnt = PathBuf::from(mnt.replace("\\040", " "));
+        if mnt.starts_with(&path_overlays) {
+            println!("Unmounting leftover overlay {}", mnt.display());
+            unmount_fuse(&mnt);
+        }
+    }
+}
+
+fn unmount_fuse(mnt: &Path) {
+    for bin in ["fusermount3", "fusermount"] {
+        if let Ok(status) = std::process::Command::new(bin)
+            .arg("-u")
+            .arg("-q")
+            .arg(mnt)
+            .status()
+            && status.success()
+        {
+            return;
+        }
+    }
+}
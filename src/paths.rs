@@ -23,28 +23,37 @@ pub static PATH_PARTY: LazyLock<PathBuf> = LazyLock::new(|| {
     PATH_LOCAL_SHARE.join("partydeck")
 });
 
-pub static PATH_STEAM: LazyLock<PathBuf> = LazyLock::new(|| {
+pub static PATH_STEAM: LazyLock<PathBuf> = LazyLock::new(|| match locate_steam() {
+    Some(steam_path) => steam_path,
+    None => PathBuf::from(get_rootpath("steam").unwrap()),
+});
+
+// Looks for Steam without asking the user, unlike PATH_STEAM
+pub fn locate_steam() -> Option<PathBuf> {
     if let Ok(steamdir) = steamlocate::SteamDir::locate() {
         let steam_path = steamdir.path().to_path_buf();
         if steam_path.exists() {
-            return steam_path;
+            return Some(steam_path);
         }
     }
 
     // Backup
     if let Ok(steam_path) = env::var("STEAM_BASE_FOLDER") {
-        return PathBuf::from(steam_path);
+        Some(PathBuf::from(steam_path))
     } else if PATH_LOCAL_SHARE.join("Steam").exists() {
-        PATH_LOCAL_SHARE.join("Steam")
+        Some(PATH_LOCAL_SHARE.join("Steam"))
     } else if PATH_HOME
         .join(".var/app/com.valvesoftware.Steam/.steam/steam")
         .exists()
     {
-        PATH_HOME.join(".var/app/com.valvesoftware.Steam/.steam/steam")
+        Some(PATH_HOME.join(".var/app/com.valvesoftware.Steam/.steam/steam"))
     } else {
-        PathBuf::from(get_rootpath("steam").unwrap())
+        None
     }
-});
+}
+
+// Dependencies installed through the dependency manager. "current" links to the active version.
+pub static PATH_DEPS: LazyLock<PathBuf> = LazyLock::new(|| PATH_PARTY.join("deps"));
 
 pub static BIN_UMU_RUN: LazyLock<PathBuf> = LazyLock::new(|| {
     let bin_candidates = [PathBuf::from("/usr/bin"), PathBuf::from("/usr/local/bin")];
@@ -56,8 +65,29 @@ pub static BIN_UMU_RUN: LazyLock<PathBuf> = LazyLock::new(|| {
         }
     }
 
+    let managed = PATH_DEPS.join("umu/current/umu-run");
+    if managed.exists() {
+        return managed;
+    }
+
     let bin = env::current_exe().unwrap().parent().unwrap().join("bin");
-    bin.join("umu-run")
+    if bin.join("umu-run").exists() {
+        return bin.join("umu-run");
+    }
+    // Not installed anywhere yet, but this is where the dependency manager will put it
+    managed
+});
+
+pub static PATH_GOLDBERG: LazyLock<PathBuf> = LazyLock::new(|| {
+    let managed = PATH_DEPS.join("goldberg/current");
+    if managed.exists() {
+        return managed;
+    }
+    let bundled = PATH_RES.join("goldberg");
+    if bundled.exists() {
+        return bundled;
+    }
+    managed
 });
 
 pub static BIN_GSC_KBM: LazyLock<PathBuf> = LazyLock::new(|| {
@@ -0,0 +1,182 @@
+use crate::app::{PartyConfig, PrefixMode};
+use crate::game::Game;
+use crate::handler::Handler;
+use crate::paths::*;
+use crate::util::{copy_dir_recursive, dir_size, set_progress};
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+pub struct PrefixInfo {
+    pub label: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub verbs: Vec<String>,
+}
+
+// Stored as partydeck.json inside each prefix, recording what's been installed into it
+#[derive(Serialize, Deserialize, Default)]
+pub struct PrefixManifest {
+    #[serde(default)]
+    pub verbs: Vec<String>,
+}
+
+pub fn load_prefix_manifest(path: &Path) -> PrefixManifest {
+    if let Ok(file) = File::open(path.join("partydeck.json"))
+        && let Ok(manifest) = serde_json::from_reader(BufReader::new(file))
+    {
+        return manifest;
+    }
+    PrefixManifest::default()
+}
+
+fn save_prefix_manifest(path: &Path, manifest: &PrefixManifest) -> Result<(), Box<dyn Error>> {
+    let file = File::create(path.join("partydeck.json"))?;
+    serde_json::to_writer_pretty(file, manifest)?;
+    Ok(())
+}
+
+// Installs the handler's winetricks verbs that the prefix doesn't have yet, one at a time
+// so that a failure doesn't lose track of the ones that went through
+pub fn apply_wine_verbs(
+    h: &Handler,
+    path: &PathBuf,
+    protonpath: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut manifest = load_prefix_manifest(path);
+    let missing: Vec<&String> = h
+        .wine_verbs
+        .iter()
+        .filter(|v| !manifest.verbs.contains(v))
+        .collect();
+
+    for (i, verb) in missing.iter().enumerate() {
+        set_progress(&format!(
+            "Installing {verb} into prefix ({}/{})...\n\nThis only needs to be done once.",
+            i + 1,
+            missing.len()
+        ));
+        let status = std::process::Command::new(&*BIN_UMU_RUN)
+            .env("WINEPREFIX", path)
+            .env("PROTONPATH", protonpath)
+            .arg("winetricks")
+            .arg("-q")
+            .arg(verb.as_str())
+            .status()?;
+        if !status.success() {
+            return Err(format!("winetricks failed to install {verb} ({status})").into());
+        }
+        manifest.verbs.push(verb.to_string());
+        save_prefix_manifest(path, &manifest)?;
+    }
+    Ok(())
+}
+
+pub fn path_shared_prefix() -> PathBuf {
+    PATH_PARTY.join("pfx")
+}
+
+pub fn path_prefix_template() -> PathBuf {
+    PATH_PARTY.join("pfx_template")
+}
+
+// Per-profile prefixes live inside the profile, so they go away along with guest profiles
+pub fn instance_prefix(game: &Game, cfg: &PartyConfig, profname: &str) -> PathBuf {
+    let Game::HandlerRef(h) = game else {
+        return path_shared_prefix();
+    };
+    match cfg.prefix_mode {
+        PrefixMode::Shared => path_shared_prefix(),
+        PrefixMode::PerGame => PATH_PARTY.join("prefixes").join(&h.uid),
+        PrefixMode::PerProfile => PATH_PARTY
+            .join("profiles")
+            .join(profname)
+            .join("pfx")
+            .join(&h.uid),
+    }
+}
+
+// Creates the prefix folder, cloning the template prefix into it if there is one.
+// Proton fills in anything that's missing on first launch.
+pub fn prepare_prefix(path: &PathBuf) -> Result<(), Box<dyn Error>> {
+    if path.exists() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(path)?;
+
+    let template = path_prefix_template();
+    if template.exists() && path != &template {
+        println!("Creating prefix {} from template", path.display());
+        copy_dir_recursive(&template, path, false, false)?;
+    }
+    Ok(())
+}
+
+pub fn scan_prefixes() -> Vec<PrefixInfo> {
+    let mut out = Vec::new();
+
+    let mut push = |label: String, path: PathBuf| {
+        if path.is_dir() {
+            out.push(PrefixInfo {
+                label,
+                size: dir_size(&path),
+                verbs: load_prefix_manifest(&path).verbs,
+                path,
+            });
+        }
+    };
+
+    push("Template".to_string(), path_prefix_template());
+    push("Shared".to_string(), path_shared_prefix());
+
+    if let Ok(entries) = std::fs::read_dir(PATH_PARTY.join("prefixes")) {
+        for entry in entries.flatten() {
+            let uid = entry.file_name().to_string_lossy().to_string();
+            push(uid, entry.path());
+        }
+    }
+
+    if let Ok(profiles) = std::fs::read_dir(PATH_PARTY.join("profiles")) {
+        for profile in profiles.flatten() {
+            let profname = profile.file_name().to_string_lossy().to_string();
+            let Ok(entries) = std::fs::read_dir(profile.path().join("pfx")) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let uid = entry.file_name().to_string_lossy().to_string();
+                push(format!("{uid} ({profname})"), entry.path());
+            }
+        }
+    }
+
+    out
+}
+
+// Deletes a prefix. The shared one is re-created empty, the rest are re-created on next launch.
+pub fn reset_prefix(path: &PathBuf) -> Result<(), Box<dyn Error>> {
+    if !path.starts_with(&*PATH_PARTY) {
+        return Err(format!("{} is not a PartyDeck prefix", path.display()).into());
+    }
+    std::fs::remove_dir_all(path)?;
+    if path == &path_shared_prefix() {
+        std::fs::create_dir_all(path)?;
+    }
+    Ok(())
+}
+
+// Copies an existing prefix to be used as the base for new ones
+pub fn set_prefix_template(src: &PathBuf) -> Result<(), Box<dyn Error>> {
+    let template = path_prefix_template();
+    if src == &template {
+        return Ok(());
+    }
+    if template.exists() {
+        std::fs::remove_dir_all(&template)?;
+    }
+    std::fs::create_dir_all(&template)?;
+    copy_dir_recursive(src, &template, false, false)?;
+    Ok(())
+}
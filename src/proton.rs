@@ -0,0 +1,137 @@
+use crate::paths::*;
+use crate::util::steam_library_paths;
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+// Names that UMU resolves (and downloads if needed) by itself
+const UMU_KEYWORDS: [&str; 4] = ["GE-Proton", "GE-Latest", "UMU-Proton", "UMU-Latest"];
+
+#[derive(Clone)]
+pub struct ProtonBuild {
+    pub name: String,
+    pub path: PathBuf,
+    pub version: String,
+    pub source: String,
+}
+
+impl ProtonBuild {
+    pub fn display(&self) -> String {
+        if self.version.is_empty() || self.version == self.name {
+            format!("{} [{}]", self.name, self.source)
+        } else {
+            format!("{} ({}) [{}]", self.name, self.version, self.source)
+        }
+    }
+}
+
+// The version file looks like "1712345678 GE-Proton9-2" (timestamp, then version)
+fn read_proton_version(path: &Path) -> String {
+    let Ok(contents) = std::fs::read_to_string(path.join("version")) else {
+        return String::new();
+    };
+    let contents = contents.trim();
+    match contents.split_once(' ') {
+        Some((_, version)) => version.trim().to_string(),
+        None => contents.to_string(),
+    }
+}
+
+fn scan_dir(dir: &PathBuf, source: &str, prefix: Option<&str>, out: &mut Vec<ProtonBuild>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(prefix) = prefix
+            && !name.starts_with(prefix)
+        {
+            continue;
+        }
+        let path = entry.path();
+        if !path.join("proton").exists() {
+            continue;
+        }
+        if out.iter().any(|b| b.path == path) {
+            continue;
+        }
+        out.push(ProtonBuild {
+            version: read_proton_version(&path),
+            name,
+            path,
+            source: source.to_string(),
+        });
+    }
+}
+
+pub fn scan_proton_builds() -> Vec<ProtonBuild> {
+    let mut out = Vec::new();
+
+    scan_dir(
+        &PATH_STEAM.join("compatibilitytools.d"),
+        "compatibilitytools.d",
+        None,
+        &mut out,
+    );
+    scan_dir(
+        &PathBuf::from("/usr/share/steam/compatibilitytools.d"),
+        "system",
+        None,
+        &mut out,
+    );
+
+    for library in steam_library_paths() {
+        scan_dir(
+            &library.join("steamapps/common"),
+            "Steam",
+            Some("Proton"),
+            &mut out,
+        );
+    }
+
+    // Builds UMU downloaded on its own
+    scan_dir(&PATH_LOCAL_SHARE.join("umu"), "UMU", None, &mut out);
+    scan_dir(
+        &PATH_LOCAL_SHARE.join("umu/compatibilitytools"),
+        "UMU",
+        None,
+        &mut out,
+    );
+
+    out.sort_by_key(|b| std::cmp::Reverse(b.name.to_lowercase()));
+    out
+}
+
+pub fn is_umu_keyword(version: &str) -> bool {
+    version.is_empty() || UMU_KEYWORDS.contains(&version)
+}
+
+// Turns the Proton setting into a PROTONPATH value, failing early if a specific build is missing
+pub fn resolve_proton(version: &str) -> Result<String, Box<dyn Error>> {
+    if version.is_empty() {
+        return Ok("GE-Proton".to_string());
+    }
+    if is_umu_keyword(version) {
+        return Ok(version.to_string());
+    }
+
+    if version.starts_with('/') {
+        let path = PathBuf::from(version);
+        if path.join("proton").exists() {
+            return Ok(version.to_string());
+        }
+        return Err(format!(
+            "Proton build not found at {version}. Pick an installed build in Settings, or leave it on GE-Proton to have UMU download it."
+        )
+        .into());
+    }
+
+    if let Some(build) = scan_proton_builds().into_iter().find(|b| b.name == version) {
+        return Ok(build.path.to_string_lossy().to_string());
+    }
+
+    Err(format!(
+        "Proton build \"{version}\" is not installed. Pick an installed build in Settings, or leave it on GE-Proton to have UMU download it."
+    )
+    .into())
+}
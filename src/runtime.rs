@@ -0,0 +1,237 @@
+use crate::paths::*;
+use crate::util::steam_library_paths;
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+pub trait Runtime {
+    fn name(&self) -> &str;
+    fn description(&self) -> String;
+    // Shell command placed before the game executable, already quoted
+    fn command(&self) -> String;
+    fn is_custom(&self) -> bool {
+        false
+    }
+}
+
+// Runs the game directly on the host, for handlers that don't ask for a runtime
+pub struct HostRuntime;
+
+impl Runtime for HostRuntime {
+    fn name(&self) -> &str {
+        "host"
+    }
+    fn description(&self) -> String {
+        "No runtime, runs directly on the host system".to_string()
+    }
+    fn command(&self) -> String {
+        String::new()
+    }
+}
+
+// A Steam Linux Runtime installed through Steam: scout through run.sh, and the
+// container-based ones (soldier, sniper, ...) through their _v2-entry-point
+pub struct SteamRuntime {
+    name: String,
+    entry: PathBuf,
+}
+
+impl Runtime for SteamRuntime {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn description(&self) -> String {
+        format!("Steam Linux Runtime ({})", self.entry.display())
+    }
+    fn command(&self) -> String {
+        format!("\"{}\"", self.entry.display())
+    }
+}
+
+// User-registered wrapper such as "distrobox enter arch --" or a podman command
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CustomRuntime {
+    pub name: String,
+    pub command: String,
+}
+
+impl Runtime for CustomRuntime {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn description(&self) -> String {
+        format!("Custom: {}", self.command)
+    }
+    fn command(&self) -> String {
+        self.command.clone()
+    }
+    fn is_custom(&self) -> bool {
+        true
+    }
+}
+
+pub struct RuntimeRegistry {
+    runtimes: Vec<Box<dyn Runtime>>,
+}
+
+impl RuntimeRegistry {
+    pub fn load() -> Self {
+        let mut runtimes: Vec<Box<dyn Runtime>> = vec![Box::new(HostRuntime)];
+
+        for rt in scan_steam_runtimes() {
+            if !runtimes.iter().any(|r| r.name() == rt.name()) {
+                runtimes.push(Box::new(rt));
+            }
+        }
+        for rt in load_custom_runtimes() {
+            if runtimes.iter().any(|r| r.name() == rt.name) {
+                println!(
+                    "Custom runtime {} conflicts with an existing runtime, skipping",
+                    rt.name
+                );
+                continue;
+            }
+            runtimes.push(Box::new(rt));
+        }
+
+        Self { runtimes }
+    }
+
+    pub fn runtimes(&self) -> &[Box<dyn Runtime>] {
+        &self.runtimes
+    }
+
+    // An empty name means the handler didn't ask for a runtime
+    pub fn resolve(&self, name: &str) -> Result<&dyn Runtime, Box<dyn Error>> {
+        let name = match name.is_empty() {
+            true => "host",
+            false => name,
+        };
+        if let Some(rt) = self.runtimes.iter().find(|r| r.name() == name) {
+            return Ok(rt.as_ref());
+        }
+
+        let installed = self
+            .runtimes
+            .iter()
+            .map(|r| r.name())
+            .collect::<Vec<&str>>()
+            .join(", ");
+        let hint = match name {
+            "scout" => "Install Steam to get the Steam Runtime (scout).".to_string(),
+            _ => format!(
+                "Install \"Steam Linux Runtime ({name})\" from your Steam library, or register a custom runtime named \"{name}\" in Settings."
+            ),
+        };
+        Err(format!("Runtime \"{name}\" not found. {hint}\nInstalled runtimes: {installed}").into())
+    }
+
+    // A missing Steam runtime is an error, since installing it fixes it. Any other name ran on
+    // the host before runtimes could be picked, so it still does, with a warning to show.
+    pub fn resolve_or_host(
+        &self,
+        name: &str,
+    ) -> Result<(&dyn Runtime, Option<String>), Box<dyn Error>> {
+        match self.resolve(name) {
+            Ok(rt) => Ok((rt, None)),
+            Err(err) if is_steam_runtime(name) => Err(err),
+            Err(err) => Ok((
+                self.runtimes[0].as_ref(),
+                Some(format!("{err}\n\nThe game will run on the host instead.")),
+            )),
+        }
+    }
+}
+
+// Runtimes Steam provides, as opposed to ones registered by the user
+pub fn is_steam_runtime(name: &str) -> bool {
+    ["scout", "soldier", "sniper", "medic"].contains(&name)
+}
+
+pub fn scan_steam_runtimes() -> Vec<SteamRuntime> {
+    let mut out = Vec::new();
+
+    let scout = PATH_STEAM.join("ubuntu12_32/steam-runtime/run.sh");
+    if scout.exists() {
+        out.push(SteamRuntime {
+            name: "scout".to_string(),
+            entry: scout,
+        });
+    }
+
+    for library in steam_library_paths() {
+        let Ok(entries) = std::fs::read_dir(library.join("steamapps/common")) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let dirname = entry.file_name().to_string_lossy().to_string();
+            // SteamLinuxRuntime_soldier -> soldier, SteamLinuxRuntime_sniper -> sniper, etc.
+            let Some(name) = dirname.strip_prefix("SteamLinuxRuntime_") else {
+                continue;
+            };
+            let entry_point = entry.path().join("_v2-entry-point");
+            if !entry_point.exists() {
+                continue;
+            }
+            println!(
+                "Found Steam Linux Runtime {name} at {}",
+                entry.path().display()
+            );
+            out.push(SteamRuntime {
+                name: name.to_lowercase(),
+                entry: entry_point,
+            });
+        }
+    }
+
+    out
+}
+
+pub fn load_custom_runtimes() -> Vec<CustomRuntime> {
+    if let Ok(file) = File::open(PATH_PARTY.join("runtimes.json"))
+        && let Ok(runtimes) = serde_json::from_reader::<_, Vec<CustomRuntime>>(BufReader::new(file))
+    {
+        return runtimes;
+    }
+    Vec::new()
+}
+
+pub fn add_custom_runtime(name: &str, command: &str) -> Result<(), Box<dyn Error>> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err("Runtime name must be alphanumeric".into());
+    }
+    if command.trim().is_empty() {
+        return Err("Runtime command can't be empty".into());
+    }
+    if RuntimeRegistry::load()
+        .runtimes()
+        .iter()
+        .any(|r| r.name() == name)
+    {
+        return Err(format!("A runtime named \"{name}\" already exists").into());
+    }
+
+    let mut runtimes = load_custom_runtimes();
+    runtimes.push(CustomRuntime {
+        name: name.to_string(),
+        command: command.trim().to_string(),
+    });
+    let file = File::create(PATH_PARTY.join("runtimes.json"))?;
+    serde_json::to_writer_pretty(file, &runtimes)?;
+    Ok(())
+}
+
+pub fn remove_custom_runtime(name: &str) -> Result<(), Box<dyn Error>> {
+    let mut runtimes = load_custom_runtimes();
+    runtimes.retain(|r| r.name != name);
+    let file = File::create(PATH_PARTY.join("runtimes.json"))?;
+    serde_json::to_writer_pretty(file, &runtimes)?;
+    Ok(())
+}
@@ -0,0 +1,258 @@
+use crate::handler::Handler;
+use crate::paths::*;
+use crate::snapshots::{SnapshotKind, create_snapshot, create_snapshot_from, prune_snapshots};
+use crate::util::{
+    copy_dir_recursive, create_gamesave, create_profile, get_rootpath_handler, steam_library_paths,
+};
+
+use std::error::Error;
+use std::path::PathBuf;
+
+// Somewhere a game keeps its data when played normally through Steam, and where the same data
+// goes in a profile
+pub struct SaveLocation {
+    // Relative to the profile folder
+    pub profile_rel: PathBuf,
+    pub original: PathBuf,
+}
+
+fn path_profile(profname: &str) -> PathBuf {
+    PATH_PARTY.join("profiles").join(profname)
+}
+
+// The steamuser folder of the game's own Proton prefix, in whichever library it's installed in
+fn compatdata_user(appid: &str) -> Option<PathBuf> {
+    steam_library_paths()
+        .into_iter()
+        .map(|lib| {
+            lib.join("steamapps/compatdata")
+                .join(appid)
+                .join("pfx/drive_c/users/steamuser")
+        })
+        .find(|path| path.exists())
+}
+
+// Steam Cloud files of the most recently used Steam account that has any for the game
+fn steam_cloud_dir(appid: &str) -> Option<PathBuf> {
+    let users = std::fs::read_dir(PATH_STEAM.join("userdata")).ok()?;
+    users
+        .flatten()
+        .map(|user| user.path().join(appid).join("remote"))
+        .filter(|path| path.exists())
+        .max_by_key(|path| {
+            std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .unwrap_or(std::time::UNIX_EPOCH)
+        })
+}
+
+// Works out where the handler's per-profile folders come from outside of PartyDeck. Also returns
+// notes about folders that can't be worked out, to show to the user.
+pub fn save_locations(h: &Handler) -> Result<(Vec<SaveLocation>, Vec<String>), Box<dyn Error>> {
+    let save = PathBuf::from("saves").join(&h.uid);
+    let mut out = Vec::new();
+    let mut skipped = Vec::new();
+
+    // Other programs keep their data in the same folders (even in the game's own prefix, Steam
+    // and launchers do), so only the ones the handler lists in profiles.import_paths are used
+    let mut shared = Vec::new();
+    if h.win && (h.win_unique_appdata || h.win_unique_documents) {
+        match h.steam_appid.as_deref().and_then(compatdata_user) {
+            Some(user) => {
+                shared.push((h.win_unique_appdata, "_AppData", user.join("AppData")));
+                shared.push((h.win_unique_documents, "_Documents", user.join("Documents")));
+            }
+            None => skipped.push(
+                "The game's Proton prefix wasn't found in your Steam libraries, so AppData and Documents were skipped.".to_string(),
+            ),
+        }
+    }
+    if !h.win {
+        shared.push((
+            h.linux_unique_localshare,
+            "_share",
+            PATH_LOCAL_SHARE.clone(),
+        ));
+        shared.push((h.linux_unique_config, "_config", PATH_HOME.join(".config")));
+    }
+    for (enabled, dir, original) in shared {
+        if !enabled {
+            continue;
+        }
+        let listed: Vec<&str> = h
+            .import_paths
+            .iter()
+            .filter_map(|p| p.strip_prefix(dir)?.strip_prefix('/'))
+            .filter(|p| !p.is_empty())
+            .collect();
+        if listed.is_empty() {
+            skipped.push(format!(
+                "The handler doesn't say which folders in {} belong to the game, so they were skipped.",
+                original.display()
+            ));
+        }
+        for rel in listed {
+            out.push(SaveLocation {
+                profile_rel: save.join(dir).join(rel),
+                original: original.join(rel),
+            });
+        }
+    }
+
+    if !h.flatpak.is_empty() {
+        out.push(SaveLocation {
+            profile_rel: save.join("_flatpak"),
+            original: PATH_HOME.join(".var/app").join(&h.flatpak),
+        });
+    }
+
+    let game_paths: Vec<&String> = h
+        .game_unique_paths
+        .iter()
+        .filter(|p| !p.is_empty())
+        .collect();
+    if !game_paths.is_empty() {
+        let gamedir = PathBuf::from(get_rootpath_handler(h)?);
+        for path in game_paths {
+            out.push(SaveLocation {
+                profile_rel: save.join(path),
+                original: gamedir.join(path),
+            });
+        }
+    }
+
+    // Goldberg keeps Steam Cloud files in <appid>/remote, like Steam's own userdata folder
+    if let Some(appid) = &h.steam_appid
+        && let Some(remote) = steam_cloud_dir(appid)
+    {
+        out.push(SaveLocation {
+            profile_rel: PathBuf::from("steam").join(appid).join("remote"),
+            original: remote,
+        });
+    }
+
+    Ok((out, skipped))
+}
+
+fn copy_location(src: &PathBuf, dest: &PathBuf) -> Result<(), Box<dyn Error>> {
+    if src.is_dir() {
+        std::fs::create_dir_all(dest)?;
+        copy_dir_recursive(src, dest, false, true)?;
+    } else {
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(src, dest)?;
+    }
+    Ok(())
+}
+
+// Copies the game's data from regular Steam play into the profile, replacing what the profile
+// had for the game after taking a snapshot of it. Returns the notes from save_locations.
+pub fn import_existing_save(profname: &str, h: &Handler) -> Result<Vec<String>, Box<dyn Error>> {
+    let (locations, skipped) = save_locations(h)?;
+    let found: Vec<SaveLocation> = locations
+        .into_iter()
+        .filter(|loc| loc.original.exists())
+        .collect();
+    if found.is_empty() {
+        let mut err = "No existing save data was found for this game.".to_string();
+        for note in &skipped {
+            err.push_str(&format!("\n{note}"));
+        }
+        return Err(err.into());
+    }
+
+    create_profile(profname)?;
+    create_gamesave(profname, h)?;
+    create_snapshot(profname, h, "", SnapshotKind::Import)?;
+
+    let path_prof = path_profile(profname);
+    for loc in &found {
+        let dest = path_prof.join(&loc.profile_rel);
+        println!(
+            "Importing {} into {}",
+            loc.original.display(),
+            dest.display()
+        );
+        if dest.is_dir() {
+            std::fs::remove_dir_all(&dest)?;
+        } else if dest.exists() {
+            std::fs::remove_file(&dest)?;
+        }
+        copy_location(&loc.original, &dest)?;
+    }
+    Ok(skipped)
+}
+
+// Snapshots what's at the given locations outside of PartyDeck, laid out the way the profile
+// keeps it, so restoring the snapshot brings it into the profile
+fn backup_originals(
+    profname: &str,
+    h: &Handler,
+    locations: &[&SaveLocation],
+) -> Result<PathBuf, Box<dyn Error>> {
+    let save = PathBuf::from("saves").join(&h.uid);
+    let sources: Vec<(PathBuf, PathBuf)> = locations
+        .iter()
+        .map(|loc| {
+            // saves/<uid>/... goes in save/, steam/<appid>/... in steam/
+            let name = match loc.profile_rel.strip_prefix(&save) {
+                Ok(rel) => PathBuf::from("save").join(rel),
+                Err(_) => {
+                    PathBuf::from("steam").join(loc.profile_rel.iter().skip(2).collect::<PathBuf>())
+                }
+            };
+            (loc.original.clone(), name)
+        })
+        .collect();
+    let info = create_snapshot_from(profname, h, "", SnapshotKind::Export, &sources)?;
+    Ok(info.path)
+}
+
+// Replaces the game's data outside of PartyDeck with the profile's, after taking a snapshot of
+// what was there. Automatic snapshots beyond the newest `keep` are removed afterwards.
+pub fn export_save_to_steam(
+    profname: &str,
+    h: &Handler,
+    keep: usize,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let (locations, mut notes) = save_locations(h)?;
+    let path_prof = path_profile(profname);
+    let exporting: Vec<&SaveLocation> = locations
+        .iter()
+        .filter(|loc| path_prof.join(&loc.profile_rel).exists())
+        .collect();
+    if exporting.is_empty() {
+        return Err(format!("{profname} has no save data for this game to export.").into());
+    }
+
+    let backup = backup_originals(profname, h, &exporting)?;
+    println!("Backed up the existing save to {}", backup.display());
+    if keep > 0 {
+        prune_snapshots(profname, &h.uid, keep)?;
+    }
+    for loc in &exporting {
+        let src = path_prof.join(&loc.profile_rel);
+        println!("Exporting {} to {}", src.display(), loc.original.display());
+        // Merging would leave files from the old save mixed in with the profile's
+        match loc.original.symlink_metadata() {
+            Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(&loc.original)?,
+            Ok(_) => std::fs::remove_file(&loc.original)?,
+            Err(_) => {}
+        }
+        copy_location(&src, &loc.original)?;
+    }
+    notes.push(
+        "The save that was there before was kept as a snapshot of the profile, so it can be brought back from the Profiles page.".to_string(),
+    );
+    if let Some(appid) = &h.steam_appid {
+        let remote = path_prof.join("steam").join(appid).join("remote");
+        if remote.exists() && !locations.iter().any(|l| l.profile_rel.starts_with("steam")) {
+            notes.push(
+                "Steam Cloud files weren't exported, since the game hasn't been run through Steam on this computer.".to_string(),
+            );
+        }
+    }
+    Ok(notes)
+}
@@ -0,0 +1,379 @@
+use crate::handler::Handler;
+use crate::paths::*;
+use crate::util::sha256_file;
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+// A snapshot is a <id>.tar.gz holding the game's save folder under "save/" and Goldberg's data
+// for the game under "steam/", next to a <id>.json describing it. They live in
+// snapshots/<profile>/<uid>, outside the profile so restoring can't touch them.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum SnapshotKind {
+    // Taken by the user, never removed automatically
+    Manual,
+    // The rest are taken by PartyDeck, and the retention policy keeps the newest few of each
+    Launch,
+    Restore,
+    Import,
+    // What the game had outside of PartyDeck before a profile's save was exported over it
+    Export,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SnapshotInfo {
+    pub label: String,
+    // Seconds since the Unix epoch
+    pub created: u64,
+    pub kind: SnapshotKind,
+    pub size: u64,
+    #[serde(default)]
+    pub appid: Option<String>,
+
+    #[serde(skip)]
+    pub profile: String,
+    #[serde(skip)]
+    pub uid: String,
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+#[derive(PartialEq)]
+pub enum SnapshotChange {
+    // Files the snapshot has that the current save doesn't, and so on
+    Removed,
+    Added,
+    Changed,
+}
+
+pub fn path_snapshots(profname: &str) -> PathBuf {
+    PATH_PARTY.join("snapshots").join(profname)
+}
+
+fn path_game_save(profname: &str, uid: &str) -> PathBuf {
+    PATH_PARTY
+        .join("profiles")
+        .join(profname)
+        .join("saves")
+        .join(uid)
+}
+
+fn path_game_steam(profname: &str, appid: &Option<String>) -> Option<PathBuf> {
+    appid.as_ref().map(|appid| {
+        PATH_PARTY
+            .join("profiles")
+            .join(profname)
+            .join("steam")
+            .join(appid)
+    })
+}
+
+fn has_files(path: &PathBuf) -> bool {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .any(|e| !e.file_type().is_dir())
+}
+
+// Newest first, across every game the profile has snapshots of
+pub fn list_snapshots(profname: &str) -> Vec<SnapshotInfo> {
+    let mut out = Vec::new();
+    let Ok(games) = std::fs::read_dir(path_snapshots(profname)) else {
+        return out;
+    };
+    for game in games.flatten() {
+        let uid = game.file_name().to_string_lossy().to_string();
+        let Ok(entries) = std::fs::read_dir(game.path()) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().unwrap_or_default() != "json" {
+                continue;
+            }
+            let Ok(file) = File::open(&path) else {
+                continue;
+            };
+            let Ok(mut info) = serde_json::from_reader::<_, SnapshotInfo>(BufReader::new(file))
+            else {
+                continue;
+            };
+            info.profile = profname.to_string();
+            info.uid = uid.clone();
+            info.path = path.with_extension("tar.gz");
+            if info.path.exists() {
+                out.push(info);
+            }
+        }
+    }
+    out.sort_by_key(|s| std::cmp::Reverse(s.created));
+    out
+}
+
+// Returns None if the profile has nothing saved for the game yet
+pub fn create_snapshot(
+    profname: &str,
+    h: &Handler,
+    label: &str,
+    kind: SnapshotKind,
+) -> Result<Option<SnapshotInfo>, Box<dyn Error>> {
+    let path_save = path_game_save(profname, &h.uid);
+    let path_steam = path_game_steam(profname, &h.steam_appid).filter(|p| p.exists());
+    if !has_files(&path_save) && !path_steam.as_ref().is_some_and(has_files) {
+        return Ok(None);
+    }
+    let mut sources = vec![(path_save, PathBuf::from("save"))];
+    if let Some(path_steam) = path_steam {
+        sources.push((path_steam, PathBuf::from("steam")));
+    }
+    let info = create_snapshot_from(profname, h, label, kind, &sources)?;
+    Ok(Some(info))
+}
+
+// Archives each file or folder under the given name, which should be in save/ or steam/
+// to be restorable
+pub fn create_snapshot_from(
+    profname: &str,
+    h: &Handler,
+    label: &str,
+    kind: SnapshotKind,
+    sources: &[(PathBuf, PathBuf)],
+) -> Result<SnapshotInfo, Box<dyn Error>> {
+    let dir = path_snapshots(profname).join(&h.uid);
+    std::fs::create_dir_all(&dir)?;
+    let created = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let mut id = created.to_string();
+    let mut n = 1;
+    while dir.join(format!("{id}.json")).exists() {
+        n += 1;
+        id = format!("{created}-{n}");
+    }
+    let path = dir.join(format!("{id}.tar.gz"));
+    println!("Taking snapshot of {} for {profname}", h.uid);
+
+    let mut tar = tar::Builder::new(GzEncoder::new(File::create(&path)?, Compression::default()));
+    tar.follow_symlinks(false);
+    for (src, name) in sources {
+        if src.is_dir() {
+            tar.append_dir_all(name, src)?;
+        } else if src.exists() {
+            tar.append_path_with_name(src, name)?;
+        }
+    }
+    tar.into_inner()?.finish()?;
+
+    let info = SnapshotInfo {
+        label: label.to_string(),
+        created,
+        kind,
+        size: std::fs::metadata(&path)?.len(),
+        appid: h.steam_appid.clone(),
+        profile: profname.to_string(),
+        uid: h.uid.clone(),
+        path,
+    };
+    let file = File::create(info.path.with_extension("").with_extension("json"))?;
+    serde_json::to_writer_pretty(file, &info)?;
+    Ok(info)
+}
+
+// Keeps the newest `keep` of each kind of automatic snapshot of a game; manual ones are never
+// removed
+pub fn prune_snapshots(profname: &str, uid: &str, keep: usize) -> Result<(), Box<dyn Error>> {
+    let snapshots = list_snapshots(profname);
+    for kind in [
+        SnapshotKind::Launch,
+        SnapshotKind::Restore,
+        SnapshotKind::Import,
+        SnapshotKind::Export,
+    ] {
+        let old = snapshots
+            .iter()
+            .filter(|s| s.uid == uid && s.kind == kind)
+            .skip(keep);
+        for snapshot in old {
+            delete_snapshot(snapshot)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn delete_snapshot(snapshot: &SnapshotInfo) -> Result<(), Box<dyn Error>> {
+    if !snapshot.path.starts_with(PATH_PARTY.join("snapshots")) {
+        return Err("Not a snapshot".into());
+    }
+    std::fs::remove_file(&snapshot.path)?;
+    std::fs::remove_file(snapshot.path.with_extension("").with_extension("json"))?;
+    Ok(())
+}
+
+// Hashes of the files in a folder, keyed by their path under it with the given prefix
+fn hash_dir(dir: &PathBuf, prefix: &str, out: &mut BTreeMap<String, String>) {
+    for entry in walkdir::WalkDir::new(dir).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(rel) = entry.path().strip_prefix(dir) else {
+            continue;
+        };
+        if let Ok(hash) = sha256_file(&entry.path().to_path_buf()) {
+            out.insert(format!("{prefix}/{}", rel.display()), hash);
+        }
+    }
+}
+
+// Lists the files that restoring the snapshot would bring back, drop or change
+pub fn diff_snapshot(
+    snapshot: &SnapshotInfo,
+) -> Result<Vec<(String, SnapshotChange)>, Box<dyn Error>> {
+    diff_archive(
+        &snapshot.path,
+        &path_game_save(&snapshot.profile, &snapshot.uid),
+        path_game_steam(&snapshot.profile, &snapshot.appid).as_ref(),
+    )
+}
+
+fn diff_archive(
+    path: &PathBuf,
+    path_save: &PathBuf,
+    path_steam: Option<&PathBuf>,
+) -> Result<Vec<(String, SnapshotChange)>, Box<dyn Error>> {
+    let mut in_snapshot = BTreeMap::new();
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry
+            .path()?
+            .to_string_lossy()
+            .trim_end_matches('/')
+            .to_string();
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut entry, &mut hasher)?;
+        let hash = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        in_snapshot.insert(path, hash);
+    }
+
+    let mut current = BTreeMap::new();
+    hash_dir(path_save, "save", &mut current);
+    if let Some(path_steam) = path_steam {
+        hash_dir(path_steam, "steam", &mut current);
+    }
+
+    let mut out = Vec::new();
+    for (path, hash) in &in_snapshot {
+        match current.get(path) {
+            None => out.push((path.clone(), SnapshotChange::Removed)),
+            Some(current_hash) if current_hash != hash => {
+                out.push((path.clone(), SnapshotChange::Changed))
+            }
+            Some(_) => {}
+        }
+    }
+    for path in current.keys() {
+        if !in_snapshot.contains_key(path) {
+            out.push((path.clone(), SnapshotChange::Added));
+        }
+    }
+    out.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(out)
+}
+
+// Replaces the game's current save with the snapshot, taking a snapshot of it first
+pub fn restore_snapshot(snapshot: &SnapshotInfo, h: &Handler) -> Result<(), Box<dyn Error>> {
+    create_snapshot(&snapshot.profile, h, "", SnapshotKind::Restore)?;
+
+    let staging = path_snapshots(&snapshot.profile).join(".restore");
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::create_dir_all(&staging)?;
+    compress_tools::uncompress_archive(
+        File::open(&snapshot.path)?,
+        &staging,
+        compress_tools::Ownership::Ignore,
+    )?;
+
+    let path_save = path_game_save(&snapshot.profile, &snapshot.uid);
+    if path_save.exists() {
+        std::fs::remove_dir_all(&path_save)?;
+    }
+    if staging.join("save").exists() {
+        std::fs::rename(staging.join("save"), &path_save)?;
+    } else {
+        std::fs::create_dir_all(&path_save)?;
+    }
+
+    if let Some(path_steam) = path_game_steam(&snapshot.profile, &snapshot.appid) {
+        if path_steam.exists() {
+            std::fs::remove_dir_all(&path_steam)?;
+        }
+        if staging.join("steam").exists() {
+            if let Some(parent) = path_steam.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(staging.join("steam"), &path_steam)?;
+        }
+    }
+
+    std::fs::remove_dir_all(&staging)?;
+    println!("Restored snapshot {} of {}", snapshot.created, snapshot.uid);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_archive_lists_changes() {
+        let dir = std::env::temp_dir().join(format!("partydeck-snapshot-{}", std::process::id()));
+        let save = dir.join("save");
+        std::fs::create_dir_all(save.join("slots")).unwrap();
+        std::fs::write(save.join("slots/1.sav"), "old").unwrap();
+        std::fs::write(save.join("options.ini"), "same").unwrap();
+        std::fs::write(save.join("gone.sav"), "x").unwrap();
+
+        let path = dir.join("snapshot.tar.gz");
+        let mut tar = tar::Builder::new(GzEncoder::new(
+            File::create(&path).unwrap(),
+            Compression::default(),
+        ));
+        tar.append_dir_all("save", &save).unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        std::fs::write(save.join("slots/1.sav"), "new").unwrap();
+        std::fs::remove_file(save.join("gone.sav")).unwrap();
+        std::fs::write(save.join("slots/2.sav"), "added").unwrap();
+
+        let changes = diff_archive(&path, &save, None).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let changes: Vec<(&str, &SnapshotChange)> =
+            changes.iter().map(|(p, c)| (p.as_str(), c)).collect();
+        assert!(matches!(
+            changes.as_slice(),
+            [
+                ("save/gone.sav", SnapshotChange::Removed),
+                ("save/slots/1.sav", SnapshotChange::Changed),
+                ("save/slots/2.sav", SnapshotChange::Added),
+            ]
+        ));
+    }
+}
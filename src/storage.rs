@@ -0,0 +1,182 @@
+use crate::handler::path_symlink_manifest;
+use crate::paths::*;
+use crate::prefix::*;
+use crate::util::{delete_profile, dir_size};
+
+use std::error::Error;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StorageKind {
+    SymlinkFolder,
+    Overlay,
+    Prefix,
+    Profile,
+    Save,
+    Snapshot,
+    Overrides,
+}
+
+impl StorageKind {
+    pub fn title(&self) -> &str {
+        match self {
+            StorageKind::SymlinkFolder => "Game symlink folders",
+            StorageKind::Overlay => "Game overlays",
+            StorageKind::Prefix => "Wine prefixes",
+            StorageKind::Profile => "Profiles",
+            StorageKind::Save => "Game saves",
+            StorageKind::Snapshot => "Save snapshots",
+            StorageKind::Overrides => "Game settings",
+        }
+    }
+}
+
+pub struct StorageItem {
+    pub kind: StorageKind,
+    pub label: String,
+    pub path: PathBuf,
+    pub size: u64,
+    // Belongs to a handler that isn't installed anymore
+    pub orphan: bool,
+}
+
+fn subdirs(path: &PathBuf) -> Vec<(String, PathBuf)> {
+    let mut out = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(path) {
+        for entry in entries.flatten() {
+            if !entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Skips gamesyms/.manifests and the like
+            if name.starts_with('.') {
+                continue;
+            }
+            out.push((name, entry.path()));
+        }
+    }
+    out.sort();
+    out
+}
+
+// uids is the list of installed handlers, used to spot leftovers from removed ones
+pub fn scan_storage(uids: &[String]) -> Vec<StorageItem> {
+    let mut out = Vec::new();
+    let orphan = |uid: &str| !uids.iter().any(|u| u == uid);
+
+    for (uid, path) in subdirs(&PATH_PARTY.join("gamesyms")) {
+        out.push(StorageItem {
+            kind: StorageKind::SymlinkFolder,
+            orphan: orphan(&uid),
+            size: dir_size(&path),
+            label: uid,
+            path,
+        });
+    }
+    for (uid, path) in subdirs(&PATH_PARTY.join("gameoverlays")) {
+        out.push(StorageItem {
+            kind: StorageKind::Overlay,
+            orphan: orphan(&uid),
+            size: dir_size(&path),
+            label: uid,
+            path,
+        });
+    }
+
+    for pfx in scan_prefixes() {
+        let is_game_prefix = pfx.path.starts_with(PATH_PARTY.join("prefixes"))
+            || pfx.path.parent().is_some_and(|p| p.ends_with("pfx"));
+        let uid = pfx
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        out.push(StorageItem {
+            kind: StorageKind::Prefix,
+            orphan: is_game_prefix && orphan(&uid),
+            label: pfx.label,
+            path: pfx.path,
+            size: pfx.size,
+        });
+    }
+
+    for (profname, path) in subdirs(&PATH_PARTY.join("profiles")) {
+        for (uid, path_save) in subdirs(&path.join("saves")) {
+            out.push(StorageItem {
+                kind: StorageKind::Save,
+                orphan: orphan(&uid),
+                size: dir_size(&path_save),
+                label: format!("{uid} ({profname})"),
+                path: path_save,
+            });
+        }
+        out.push(StorageItem {
+            kind: StorageKind::Profile,
+            orphan: false,
+            size: dir_size(&path),
+            label: profname,
+            path,
+        });
+    }
+
+    for (profname, path) in subdirs(&PATH_PARTY.join("snapshots")) {
+        for (uid, path_snapshots) in subdirs(&path) {
+            out.push(StorageItem {
+                kind: StorageKind::Snapshot,
+                orphan: orphan(&uid),
+                size: dir_size(&path_snapshots),
+                label: format!("{uid} ({profname})"),
+                path: path_snapshots,
+            });
+        }
+    }
+
+    let mut overrides: Vec<PathBuf> = std::fs::read_dir(PATH_PARTY.join("overrides"))
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    overrides.sort();
+    for path in overrides {
+        if path.extension().unwrap_or_default() != "json" {
+            continue;
+        }
+        let uid = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        out.push(StorageItem {
+            kind: StorageKind::Overrides,
+            orphan: orphan(&uid),
+            size: std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+            label: uid,
+            path,
+        });
+    }
+
+    out
+}
+
+pub fn remove_storage_item(item: &StorageItem) -> Result<(), Box<dyn Error>> {
+    match item.kind {
+        StorageKind::Prefix => return reset_prefix(&item.path),
+        // Also takes the profile's snapshots with it
+        StorageKind::Profile => return delete_profile(&item.label),
+        StorageKind::SymlinkFolder => {
+            // The manifest is of no use without the folder it describes
+            let manifest = path_symlink_manifest(&item.label);
+            if manifest.exists() {
+                std::fs::remove_file(manifest)?;
+            }
+        }
+        _ => {}
+    }
+    if !item.path.starts_with(&*PATH_PARTY) {
+        return Err(format!("{} is not in the PartyDeck folder", item.path.display()).into());
+    }
+    match item.kind {
+        StorageKind::Overrides => std::fs::remove_file(&item.path)?,
+        _ => std::fs::remove_dir_all(&item.path)?,
+    }
+    Ok(())
+}
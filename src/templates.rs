@@ -0,0 +1,369 @@
+use crate::handler::Handler;
+use crate::util::SanitizePath;
+
+use serde_json::Value;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+// Per-instance values available to game.args and profiles.files.
+// $INSTANCE is zero-based, so $PORT is $PORT_BASE for the first player,
+// $PORT_BASE + 1 for the second, and so on.
+pub struct InstanceVars {
+    pub gamedir: String,
+    pub profile: String,
+    pub instance: usize,
+    pub width: u32,
+    pub height: u32,
+    pub port_base: u16,
+}
+
+impl InstanceVars {
+    pub fn expand(&self, s: &str) -> String {
+        if !s.contains('$') {
+            return s.to_string();
+        }
+        let port = self.port_base as usize + self.instance;
+        // Longer names first so $WIDTH doesn't eat the start of $WIDTHXHEIGHT
+        s.replace("$GAMEDIR", &self.gamedir)
+            .replace("$PROFILE", &self.profile)
+            .replace("$INSTANCE", &self.instance.to_string())
+            .replace("$WIDTHXHEIGHT", &format!("{}x{}", self.width, self.height))
+            .replace("$WIDTH", &self.width.to_string())
+            .replace("$HEIGHT", &self.height.to_string())
+            .replace("$PORT_BASE", &self.port_base.to_string())
+            .replace("$PORT", &port.to_string())
+    }
+}
+
+#[derive(Clone)]
+pub enum ProfileFileEdit {
+    Ini {
+        section: String,
+        key: String,
+        value: String,
+    },
+    Json {
+        pointer: String,
+        value: Value,
+    },
+    // Either a template file from the handler folder, or inline content
+    Text {
+        template: String,
+        content: String,
+    },
+}
+
+#[derive(Clone)]
+pub struct ProfileFile {
+    pub path: String,
+    pub edit: ProfileFileEdit,
+    // Folder templates are read from, when the file comes from a base handler or template
+    pub dir: Option<PathBuf>,
+}
+
+impl ProfileFile {
+    pub fn from_json(json: &Value) -> Option<Self> {
+        let path = json["path"].as_str()?.to_string().sanitize_path();
+        if path.is_empty() {
+            return None;
+        }
+        let str_field = |name: &str| json[name].as_str().unwrap_or_default().to_string();
+
+        let edit = match json["type"].as_str()? {
+            "ini" => ProfileFileEdit::Ini {
+                section: str_field("section"),
+                key: json["key"].as_str()?.to_string(),
+                value: str_field("value"),
+            },
+            "json" => ProfileFileEdit::Json {
+                pointer: json["pointer"].as_str()?.to_string(),
+                value: json["value"].clone(),
+            },
+            "text" => ProfileFileEdit::Text {
+                template: str_field("template").sanitize_path(),
+                content: str_field("content"),
+            },
+            _ => return None,
+        };
+        let dir = json["dir"].as_str().map(PathBuf::from);
+        Some(ProfileFile { path, edit, dir })
+    }
+}
+
+pub fn ini_set(contents: &str, section: &str, key: &str, value: &str) -> String {
+    let mut lines: Vec<String> = contents.lines().map(|l| l.to_string()).collect();
+    let new_line = format!("{key}={value}");
+
+    // An empty section name means keys at the top of the file, before any [section]
+    let mut in_section = section.is_empty();
+    let mut section_found = section.is_empty();
+    let mut insert_at: Option<usize> = if section.is_empty() { Some(0) } else { None };
+
+    for i in 0..lines.len() {
+        let trimmed = lines[i].trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            if in_section {
+                break;
+            }
+            in_section = trimmed[1..trimmed.len() - 1]
+                .trim()
+                .eq_ignore_ascii_case(section);
+            if in_section {
+                section_found = true;
+                insert_at = Some(i + 1);
+            }
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        if let Some((k, _)) = trimmed.split_once('=')
+            && k.trim().eq_ignore_ascii_case(key)
+        {
+            lines[i] = new_line;
+            return lines.join("\n") + "\n";
+        }
+        if !trimmed.is_empty() {
+            insert_at = Some(i + 1);
+        }
+    }
+
+    match (section_found, insert_at) {
+        (true, Some(i)) => lines.insert(i, new_line),
+        _ => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("[{section}]"));
+            lines.push(new_line);
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+pub fn ini_get(contents: &str, section: &str, key: &str) -> Option<String> {
+    let mut in_section = section.is_empty();
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            in_section = trimmed[1..trimmed.len() - 1]
+                .trim()
+                .eq_ignore_ascii_case(section);
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        if let Some((k, v)) = trimmed.split_once('=')
+            && k.trim().eq_ignore_ascii_case(key)
+        {
+            return Some(v.trim().to_string());
+        }
+    }
+    None
+}
+
+fn json_set(root: &mut Value, pointer: &str, value: Value) -> Result<(), Box<dyn Error>> {
+    let tokens: Vec<String> = pointer
+        .split('/')
+        .skip(1)
+        .map(|t| t.replace("~1", "/").replace("~0", "~"))
+        .collect();
+    if tokens.is_empty() {
+        *root = value;
+        return Ok(());
+    }
+
+    let mut cur = root;
+    for token in &tokens[..tokens.len() - 1] {
+        if !cur.is_object() && !cur.is_array() {
+            *cur = Value::Object(serde_json::Map::new());
+        }
+        cur = match cur {
+            Value::Array(arr) => {
+                let i: usize = token.parse()?;
+                arr.get_mut(i)
+                    .ok_or_else(|| format!("Index {i} out of range in {pointer}"))?
+            }
+            Value::Object(map) => map
+                .entry(token.clone())
+                .or_insert(Value::Object(serde_json::Map::new())),
+            _ => unreachable!(),
+        };
+    }
+
+    let last = tokens.last().unwrap();
+    match cur {
+        Value::Array(arr) => {
+            let i: usize = last.parse()?;
+            if i < arr.len() {
+                arr[i] = value;
+            } else {
+                arr.push(value);
+            }
+        }
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        _ => {
+            let mut map = serde_json::Map::new();
+            map.insert(last.clone(), value);
+            *cur = Value::Object(map);
+        }
+    }
+    Ok(())
+}
+
+// String values are expanded; if a value with a variable in it expands to a plain number or
+// boolean (e.g. "$WIDTH"), it's written as that type instead of a string. Strings without
+// variables are left as the handler wrote them.
+fn expand_json_value(value: &Value, vars: &InstanceVars) -> Value {
+    match value {
+        Value::String(s) if !s.contains('$') => value.clone(),
+        Value::String(s) => {
+            let expanded = vars.expand(s);
+            match serde_json::from_str::<Value>(&expanded) {
+                Ok(v) if v.is_number() || v.is_boolean() => v,
+                _ => Value::String(expanded),
+            }
+        }
+        Value::Array(arr) => Value::Array(arr.iter().map(|v| expand_json_value(v, vars)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), expand_json_value(v, vars)))
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+// Generates or patches the files listed in the handler's profiles.files inside the profile's save
+pub fn apply_profile_files(
+    h: &Handler,
+    vars: &InstanceVars,
+    path_save: &Path,
+) -> Result<(), Box<dyn Error>> {
+    for file in &h.profile_files {
+        let path = path_save.join(&file.path);
+        println!("Generating {} for {}", path.display(), vars.profile);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Game save files are sometimes links into the real game dir; never write through them
+        if path.is_symlink() {
+            std::fs::remove_file(&path)?;
+        }
+
+        match &file.edit {
+            ProfileFileEdit::Ini {
+                section,
+                key,
+                value,
+            } => {
+                let contents = std::fs::read_to_string(&path).unwrap_or_default();
+                let contents = ini_set(&contents, section, key, &vars.expand(value));
+                std::fs::write(&path, contents)?;
+            }
+            ProfileFileEdit::Json { pointer, value } => {
+                let mut json: Value = std::fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|s| serde_json::from_str(&s).ok())
+                    .unwrap_or(Value::Object(serde_json::Map::new()));
+                json_set(&mut json, pointer, expand_json_value(value, vars))?;
+                std::fs::write(&path, serde_json::to_string_pretty(&json)?)?;
+            }
+            ProfileFileEdit::Text { template, content } => {
+                let text = match template.is_empty() {
+                    true => content.clone(),
+                    false => std::fs::read_to_string(
+                        file.dir.as_ref().unwrap_or(&h.path_handler).join(template),
+                    )?,
+                };
+                std::fs::write(&path, vars.expand(&text))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vars() -> InstanceVars {
+        InstanceVars {
+            gamedir: "/games/test".to_string(),
+            profile: "Alice".to_string(),
+            instance: 1,
+            width: 1280,
+            height: 720,
+            port_base: 27015,
+        }
+    }
+
+    #[test]
+    fn expand_json_value_types() {
+        let value = json!({
+            "width": "$WIDTH",
+            "res": "$WIDTHXHEIGHT",
+            "port": "$PORT",
+            "name": "$PROFILE",
+            "literal": "1280",
+            "list": ["$INSTANCE", true],
+        });
+        assert_eq!(
+            expand_json_value(&value, &vars()),
+            json!({
+                "width": 1280,
+                "res": "1280x720",
+                "port": 27016,
+                "name": "Alice",
+                "literal": "1280",
+                "list": [1, true],
+            })
+        );
+    }
+
+    #[test]
+    fn ini_set_replaces_in_section() {
+        let contents = "[video]\nwidth=800\n\n[user]\nname=old\n";
+        assert_eq!(
+            ini_set(contents, "USER", "Name", "new"),
+            "[video]\nwidth=800\n\n[user]\nName=new\n"
+        );
+    }
+
+    #[test]
+    fn ini_set_adds_keys_and_sections() {
+        let contents = "[video]\nwidth=800\n\n[user]\nname=old\n";
+        assert_eq!(
+            ini_set(contents, "video", "height", "600"),
+            "[video]\nwidth=800\nheight=600\n\n[user]\nname=old\n"
+        );
+        assert_eq!(
+            ini_set(contents, "audio", "volume", "5"),
+            "[video]\nwidth=800\n\n[user]\nname=old\n\n[audio]\nvolume=5\n"
+        );
+        assert_eq!(ini_set("[a]\nb=1\n", "", "top", "x"), "top=x\n[a]\nb=1\n");
+    }
+
+    #[test]
+    fn json_set_paths() {
+        let mut root = json!({"a": {"list": [1, 2]}, "b": 1});
+        json_set(&mut root, "/a/list/0", json!(5)).unwrap();
+        json_set(&mut root, "/a/list/9", json!(3)).unwrap();
+        json_set(&mut root, "/b/c", json!("x")).unwrap();
+        json_set(&mut root, "/new~1key/deep", json!(true)).unwrap();
+        assert_eq!(
+            root,
+            json!({
+                "a": {"list": [5, 2, 3]},
+                "b": {"c": "x"},
+                "new/key": {"deep": true},
+            })
+        );
+        assert!(json_set(&mut root, "/a/list/7/x", json!(1)).is_err());
+    }
+}
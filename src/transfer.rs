@@ -0,0 +1,310 @@
+use crate::goldberg::{GoldbergProfile, unique_steam_id};
+use crate::handler::Handler;
+use crate::paths::*;
+use crate::util::{copy_dir_recursive, copy_profile_files, is_valid_profile_name};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, PathBuf};
+
+// A .pdp file is a tar.gz with manifest.json first, followed by the exported files under
+// "profile/", laid out the same way as the profile folder
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProfileArchiveManifest {
+    pub profile: String,
+    // Uids of the games included; empty if the whole profile was exported
+    #[serde(default)]
+    pub games: Vec<String>,
+    pub exported: u64,
+    pub partydeck_version: String,
+}
+
+impl ProfileArchiveManifest {
+    pub fn is_whole_profile(&self) -> bool {
+        self.games.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportMode {
+    // Adds the archive's files to the existing profile, keeping its name, Steam ID and settings
+    Merge,
+    // Throws away what the existing profile has for whatever the archive contains
+    Replace,
+    // Imports as a new profile under another name
+    Rename,
+}
+
+fn path_profile(profname: &str) -> PathBuf {
+    PATH_PARTY.join("profiles").join(profname)
+}
+
+// Exports the whole profile, or just one game's save when h is given. Per-profile Wine
+// prefixes are left out, since they're big and get recreated when needed.
+pub fn export_profile(
+    profname: &str,
+    h: Option<&Handler>,
+    dest: &PathBuf,
+) -> Result<(), Box<dyn Error>> {
+    let path_prof = path_profile(profname);
+    let manifest = ProfileArchiveManifest {
+        profile: profname.to_string(),
+        games: h.iter().map(|h| h.uid.clone()).collect(),
+        exported: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs(),
+        partydeck_version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+
+    let mut tar = tar::Builder::new(GzEncoder::new(File::create(dest)?, Compression::default()));
+    tar.follow_symlinks(false);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    tar.append_data(&mut header, "manifest.json", manifest_json.as_slice())?;
+
+    let mut add = |rel: PathBuf| -> Result<(), Box<dyn Error>> {
+        let src = path_prof.join(&rel);
+        if src.is_dir() {
+            tar.append_dir_all(PathBuf::from("profile").join(&rel), &src)?;
+        } else if src.exists() {
+            tar.append_path_with_name(&src, PathBuf::from("profile").join(&rel))?;
+        }
+        Ok(())
+    };
+    match h {
+        Some(h) => {
+            add(PathBuf::from("saves").join(&h.uid))?;
+            if let Some(appid) = &h.steam_appid {
+                add(PathBuf::from("steam").join(appid))?;
+            }
+        }
+        None => {
+            for entry in std::fs::read_dir(&path_prof)?.flatten() {
+                if entry.file_name() == "pfx" {
+                    continue;
+                }
+                add(PathBuf::from(entry.file_name()))?;
+            }
+        }
+    }
+    tar.into_inner()?.finish()?;
+
+    println!("Exported {profname} to {}", dest.display());
+    Ok(())
+}
+
+pub fn read_archive_manifest(file: &PathBuf) -> Result<ProfileArchiveManifest, Box<dyn Error>> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(file)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.as_os_str() != "manifest.json" {
+            continue;
+        }
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents)?;
+        let manifest: ProfileArchiveManifest = serde_json::from_str(&contents)?;
+        if !is_valid_profile_name(&manifest.profile)
+            || !manifest
+                .games
+                .iter()
+                .all(|uid| uid.chars().all(char::is_alphanumeric))
+        {
+            return Err("The archive's manifest is invalid".into());
+        }
+        return Ok(manifest);
+    }
+    Err("Not a PartyDeck profile archive".into())
+}
+
+// Profiles only link to files next to them, as with Goldberg's SteamID migration; links
+// anywhere else in an archive could plant access to files outside the profile
+fn check_links(dir: &PathBuf) -> Result<(), Box<dyn Error>> {
+    for entry in walkdir::WalkDir::new(dir).follow_links(false) {
+        let entry = entry?;
+        if !entry.file_type().is_symlink() {
+            continue;
+        }
+        let target = std::fs::read_link(entry.path())?;
+        if target.is_absolute() || target.components().any(|c| c == Component::ParentDir) {
+            return Err(format!(
+                "The archive has a link to somewhere outside the profile: {}",
+                entry.path().strip_prefix(dir)?.display()
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+// Imports the archive into the profile called `name`, which is the archive's own profile name
+// unless renaming
+pub fn import_profile(file: &PathBuf, mode: ImportMode, name: &str) -> Result<(), Box<dyn Error>> {
+    let manifest = read_archive_manifest(file)?;
+    if !is_valid_profile_name(name) {
+        return Err("Profile names must be alphanumeric".into());
+    }
+    let path_prof = path_profile(name);
+    if mode == ImportMode::Rename && path_prof.exists() {
+        return Err(format!("A profile named {name} already exists").into());
+    }
+
+    // tar refuses to unpack anything outside of the destination folder
+    let staging = PATH_PARTY.join("tmp/import");
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::create_dir_all(&staging)?;
+    tar::Archive::new(GzDecoder::new(File::open(file)?)).unpack(&staging)?;
+    let imported = staging.join("profile");
+    if !imported.exists() {
+        std::fs::create_dir_all(&imported)?;
+    }
+    check_links(&imported)?;
+
+    // The imported profile is put together here and only swapped in once it's complete, so a
+    // failed import leaves the existing profile as it was
+    let result = staging.join("result");
+    std::fs::create_dir_all(&result)?;
+    let replace_all = mode == ImportMode::Replace && manifest.is_whole_profile();
+    let keep_pfx = path_prof.join("pfx").exists() && !replace_all;
+    // Archives don't have Wine prefixes, so the existing one is moved over at the end
+    if path_prof.exists() && !replace_all {
+        copy_profile_files(&path_prof, &result)?;
+    }
+
+    if mode == ImportMode::Replace && !replace_all {
+        for rel in ["saves", "steam"] {
+            let Ok(entries) = std::fs::read_dir(imported.join(rel)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let existing = result.join(rel).join(entry.file_name());
+                match existing.symlink_metadata() {
+                    Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(existing)?,
+                    Ok(_) => std::fs::remove_file(existing)?,
+                    Err(_) => {}
+                }
+            }
+        }
+    }
+
+    if mode == ImportMode::Merge && path_prof.exists() {
+        // The profile keeps its own identity and look
+        let settings = imported.join("steam/settings");
+        if settings.exists() {
+            std::fs::remove_dir_all(settings)?;
+        }
+        let meta = imported.join("profile.json");
+        if meta.exists() {
+            std::fs::remove_file(meta)?;
+        }
+    }
+
+    copy_dir_recursive(&imported, &result, false, true)?;
+
+    if keep_pfx {
+        std::fs::rename(path_prof.join("pfx"), result.join("pfx"))?;
+    }
+    let old = staging.join("old");
+    if path_prof.exists() {
+        std::fs::rename(&path_prof, &old)?;
+    }
+    if let Err(err) = std::fs::rename(&result, &path_prof) {
+        if old.exists() {
+            std::fs::rename(&old, &path_prof)?;
+        }
+        if keep_pfx {
+            std::fs::rename(result.join("pfx"), path_prof.join("pfx"))?;
+        }
+        return Err(err.into());
+    }
+    std::fs::remove_dir_all(&staging)?;
+
+    let mut goldberg = GoldbergProfile::load(name);
+    // Single game archives don't carry the profile's Goldberg settings, so start from defaults
+    if goldberg.steam_id.is_empty() {
+        goldberg.steam_id = unique_steam_id(name);
+    }
+    if mode == ImportMode::Rename {
+        if goldberg.name == manifest.profile {
+            goldberg.name = name.to_string();
+        }
+        // A renamed import is a different player as far as this install is concerned
+        goldberg.steam_id = unique_steam_id(name);
+    }
+    goldberg.save(name)?;
+
+    println!("Imported {} into profile {name}", file.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_archive(name: &str, manifest: Option<&str>) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("partydeck-{name}-{}.pdp", std::process::id()));
+        let mut tar = tar::Builder::new(GzEncoder::new(
+            File::create(&path).unwrap(),
+            Compression::default(),
+        ));
+        let mut add = |file: &str, data: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, file, data).unwrap();
+        };
+        if let Some(manifest) = manifest {
+            add("manifest.json", manifest.as_bytes());
+        }
+        add("profile/saves/game/save.dat", b"save");
+        tar.into_inner().unwrap().finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn read_archive_manifest_valid() {
+        let path = write_archive(
+            "valid",
+            Some(
+                r#"{"profile":"Alice","games":["game1"],"exported":0,"partydeck_version":"0.6.0"}"#,
+            ),
+        );
+        let manifest = read_archive_manifest(&path);
+        std::fs::remove_file(&path).unwrap();
+        let manifest = manifest.unwrap();
+        assert_eq!(manifest.profile, "Alice");
+        assert_eq!(manifest.games, vec!["game1".to_string()]);
+        assert!(!manifest.is_whole_profile());
+    }
+
+    #[test]
+    fn read_archive_manifest_rejects_bad_archives() {
+        for (name, manifest) in [
+            (
+                "badname",
+                Some(r#"{"profile":"../x","exported":0,"partydeck_version":"0.6.0"}"#),
+            ),
+            (
+                "baduid",
+                Some(
+                    r#"{"profile":"Alice","games":["a/b"],"exported":0,"partydeck_version":"0.6.0"}"#,
+                ),
+            ),
+            ("missing", None),
+        ] {
+            let path = write_archive(name, manifest);
+            let result = read_archive_manifest(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert!(result.is_err(), "{name} was accepted");
+        }
+    }
+}
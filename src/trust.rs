@@ -0,0 +1,318 @@
+use crate::paths::*;
+use crate::util::sha256_file;
+
+use minisign_verify::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrustedKey {
+    pub name: String,
+    pub key: String,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum HandlerTrust {
+    Unsigned,
+    // Signature is valid, but the key isn't (or is no longer) in the trusted list
+    Untrusted,
+    // Signature doesn't match the handler archive
+    Invalid,
+    // Files in the handler folder changed after it was installed from a signed archive
+    Modified,
+    // Signed by a trusted key, but extends a template or handler that isn't
+    UntrustedBase(String),
+    Trusted(TrustedKey),
+}
+
+impl HandlerTrust {
+    pub fn badge(&self) -> String {
+        match self {
+            HandlerTrust::Unsigned => "⚠ Unsigned".to_string(),
+            HandlerTrust::Untrusted => "⚠ Untrusted".to_string(),
+            HandlerTrust::Invalid => "❌ Invalid signature".to_string(),
+            HandlerTrust::Modified => "❌ Modified since install".to_string(),
+            HandlerTrust::UntrustedBase(base) => format!("⚠ Extends untrusted {base}"),
+            HandlerTrust::Trusted(key) => format!("✔ Signed by {}", key.name),
+        }
+    }
+}
+
+// Verification result recorded at install time in trust/<uid>.json, outside the handler folder
+// so neither the handler nor edits to it can change the record.
+#[derive(Serialize, Deserialize, Default)]
+struct TrustRecord {
+    signed: bool,
+    valid: bool,
+    signer_key: Option<String>,
+    // Hashes of the handler's files when it was installed, keyed by path in the handler folder.
+    // Handlers installed before these were recorded have none, and aren't checked.
+    #[serde(default)]
+    files: Option<BTreeMap<String, String>>,
+}
+
+fn path_trust_record(uid: &str) -> PathBuf {
+    PATH_PARTY.join("trust").join(format!("{uid}.json"))
+}
+
+// The signed archive is kept next to the record, so handlers can be verified again
+// when the user adds a key
+fn path_trust_archive(uid: &str) -> PathBuf {
+    PATH_PARTY.join("trust").join(format!("{uid}.pdh"))
+}
+
+fn path_signature(file: &Path) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".minisig");
+    PathBuf::from(path)
+}
+
+// Handlers are checked on every game scan, so files are only hashed again
+// when their size or mtime changed since the last time
+// Size, mtime and hash of each file
+type HashCache = HashMap<PathBuf, (u64, SystemTime, String)>;
+
+static HASH_CACHE: LazyLock<Mutex<HashCache>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn hash_file_cached(path: &PathBuf) -> String {
+    let Ok(meta) = std::fs::metadata(path) else {
+        return String::new();
+    };
+    let mtime = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut cache = HASH_CACHE.lock().unwrap();
+    if let Some((size, time, hash)) = cache.get(path)
+        && *size == meta.len()
+        && *time == mtime
+    {
+        return hash.clone();
+    }
+    let hash = sha256_file(path).unwrap_or_default();
+    cache.insert(path.clone(), (meta.len(), mtime, hash.clone()));
+    hash
+}
+
+fn hash_handler_files(path_handler: &PathBuf) -> BTreeMap<String, String> {
+    let mut out = BTreeMap::new();
+    for entry in walkdir::WalkDir::new(path_handler).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(rel) = entry.path().strip_prefix(path_handler) else {
+            continue;
+        };
+        let hash = hash_file_cached(&entry.path().to_path_buf());
+        out.insert(rel.to_string_lossy().to_string(), hash);
+    }
+    out
+}
+
+pub fn load_trusted_keys() -> Vec<TrustedKey> {
+    if let Ok(file) = File::open(PATH_PARTY.join("trusted_keys.json"))
+        && let Ok(keys) = serde_json::from_reader::<_, Vec<TrustedKey>>(BufReader::new(file))
+    {
+        return keys;
+    }
+    Vec::new()
+}
+
+pub fn save_trusted_keys(keys: &[TrustedKey]) -> Result<(), Box<dyn Error>> {
+    let file = File::create(PATH_PARTY.join("trusted_keys.json"))?;
+    serde_json::to_writer_pretty(file, keys)?;
+    Ok(())
+}
+
+// Accepts either the bare base64 key or the full contents of a minisign .pub file
+fn parse_public_key(key: &str) -> Result<PublicKey, Box<dyn Error>> {
+    let key = key.trim();
+    let pk = match key.contains('\n') {
+        true => PublicKey::decode(key)?,
+        false => PublicKey::from_base64(key)?,
+    };
+    Ok(pk)
+}
+
+pub fn add_trusted_key(name: &str, key: &str) -> Result<(), Box<dyn Error>> {
+    // Normalize to the bare base64 line so keys compare equal however they were pasted
+    let key = key
+        .trim()
+        .lines()
+        .last()
+        .ok_or("Empty key")?
+        .trim()
+        .to_string();
+    parse_public_key(&key)?;
+
+    let mut keys = load_trusted_keys();
+    if keys.iter().any(|k| k.key == key) {
+        return Err("This key is already trusted".into());
+    }
+    keys.push(TrustedKey {
+        name: name.to_string(),
+        key,
+    });
+    save_trusted_keys(&keys)?;
+    reverify_handlers();
+    Ok(())
+}
+
+pub fn remove_trusted_key(key: &str) -> Result<(), Box<dyn Error>> {
+    let mut keys = load_trusted_keys();
+    keys.retain(|k| k.key != key);
+    save_trusted_keys(&keys)
+}
+
+// Checks the detached signature (<file>.minisig) of a .pdh archive against the trusted keys
+pub fn verify_handler_file(file: &PathBuf) -> HandlerTrust {
+    let sig_path = path_signature(file);
+
+    let Ok(sig_text) = std::fs::read_to_string(&sig_path) else {
+        println!("No signature found for {}", file.display());
+        return HandlerTrust::Unsigned;
+    };
+    let Ok(signature) = Signature::decode(&sig_text) else {
+        println!("Couldn't decode signature {}", sig_path.display());
+        return HandlerTrust::Invalid;
+    };
+    let Ok(bytes) = std::fs::read(file) else {
+        return HandlerTrust::Invalid;
+    };
+
+    let keys = load_trusted_keys();
+    for key in &keys {
+        let Ok(pk) = parse_public_key(&key.key) else {
+            continue;
+        };
+        if pk.verify(&bytes, &signature, false).is_ok() {
+            println!("Handler signed by trusted key {}", key.name);
+            return HandlerTrust::Trusted(key.clone());
+        }
+    }
+
+    // None of the trusted keys match, so we can't tell a forged signature
+    // from one made by an unknown author
+    HandlerTrust::Untrusted
+}
+
+pub fn write_handler_trust(
+    uid: &str,
+    path_handler: &PathBuf,
+    file: &PathBuf,
+    trust: &HandlerTrust,
+) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(PATH_PARTY.join("trust"))?;
+    remove_trust_archive(uid);
+    if matches!(
+        trust,
+        HandlerTrust::Untrusted | HandlerTrust::UntrustedBase(_)
+    ) {
+        let path_archive = path_trust_archive(uid);
+        std::fs::copy(file, &path_archive)?;
+        std::fs::copy(path_signature(file), path_signature(&path_archive))?;
+    }
+
+    let record = match trust {
+        HandlerTrust::Unsigned => TrustRecord::default(),
+        HandlerTrust::Untrusted | HandlerTrust::UntrustedBase(_) => TrustRecord {
+            signed: true,
+            valid: true,
+            signer_key: None,
+            files: Some(hash_handler_files(path_handler)),
+        },
+        HandlerTrust::Invalid | HandlerTrust::Modified => TrustRecord {
+            signed: true,
+            valid: false,
+            signer_key: None,
+            files: None,
+        },
+        HandlerTrust::Trusted(key) => TrustRecord {
+            signed: true,
+            valid: true,
+            signer_key: Some(key.key.clone()),
+            files: Some(hash_handler_files(path_handler)),
+        },
+    };
+    let file = File::create(path_trust_record(uid))?;
+    serde_json::to_writer_pretty(file, &record)?;
+    Ok(())
+}
+
+fn remove_trust_archive(uid: &str) {
+    let path_archive = path_trust_archive(uid);
+    let _ = std::fs::remove_file(path_signature(&path_archive));
+    let _ = std::fs::remove_file(path_archive);
+}
+
+pub fn remove_handler_trust(uid: &str) {
+    let _ = std::fs::remove_file(path_trust_record(uid));
+    remove_trust_archive(uid);
+}
+
+// Checks the kept archives of untrusted handlers again, so a newly added key
+// trusts the handlers it signed
+fn reverify_handlers() {
+    let Ok(entries) = std::fs::read_dir(PATH_PARTY.join("trust")) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().unwrap_or_default() != "json" {
+            continue;
+        }
+        let Ok(file) = File::open(&path) else {
+            continue;
+        };
+        let Ok(mut record) = serde_json::from_reader::<_, TrustRecord>(BufReader::new(file)) else {
+            continue;
+        };
+        if !record.signed || !record.valid || record.signer_key.is_some() {
+            continue;
+        }
+        let uid = path.file_stem().unwrap_or_default().to_string_lossy();
+        if let HandlerTrust::Trusted(key) = verify_handler_file(&path_trust_archive(&uid)) {
+            record.signer_key = Some(key.key);
+            if let Ok(file) = File::create(&path) {
+                let _ = serde_json::to_writer_pretty(file, &record);
+            }
+            remove_trust_archive(&uid);
+        }
+    }
+}
+
+// Handlers that were signed by a key the user has since removed become untrusted
+pub fn read_handler_trust(uid: &str, path_handler: &PathBuf) -> HandlerTrust {
+    let Ok(file) = File::open(path_trust_record(uid)) else {
+        return HandlerTrust::Unsigned;
+    };
+    let record: TrustRecord = serde_json::from_reader(BufReader::new(file)).unwrap_or_default();
+
+    if !record.signed {
+        return HandlerTrust::Unsigned;
+    }
+    if !record.valid {
+        return HandlerTrust::Invalid;
+    }
+    // The signature only covers the archive, so edits made afterwards void it
+    if let Some(files) = &record.files
+        && *files != hash_handler_files(path_handler)
+    {
+        println!(
+            "Handler at {} was modified since install",
+            path_handler.display()
+        );
+        return HandlerTrust::Modified;
+    }
+    match record.signer_key {
+        Some(signer) => load_trusted_keys()
+            .into_iter()
+            .find(|k| k.key == signer)
+            .map(HandlerTrust::Trusted)
+            .unwrap_or(HandlerTrust::Untrusted),
+        _ => HandlerTrust::Untrusted,
+    }
+}
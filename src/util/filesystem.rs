@@ -2,11 +2,22 @@ use crate::handler::Handler;
 use crate::paths::*;
 use rfd::FileDialog;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use std::error::Error;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
 
+// Makes way for an entry being copied over whatever is at path. Symlinks are removed rather
+// than followed, so copying can't write through them.
+fn remove_existing(path: &PathBuf) -> std::io::Result<()> {
+    match path.symlink_metadata() {
+        Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(_) => Ok(()),
+    }
+}
+
 pub fn copy_dir_recursive(
     src: &PathBuf,
     dest: &PathBuf,
@@ -33,16 +44,22 @@ pub fn copy_dir_recursive(
         // );
 
         if entry.file_type().is_dir() {
+            if overwrite_dest && new_path.symlink_metadata().is_ok_and(|m| !m.is_dir()) {
+                std::fs::remove_file(&new_path)?;
+            }
             std::fs::create_dir_all(&new_path)?;
         } else if entry.file_type().is_symlink() {
             let symlink_src = std::fs::read_link(entry.path())?;
+            if overwrite_dest {
+                remove_existing(&new_path)?;
+            }
             std::os::unix::fs::symlink(symlink_src, new_path)?;
         } else {
             if let Some(parent) = new_path.parent() {
                 std::fs::create_dir_all(parent)?;
             }
-            if new_path.exists() && overwrite_dest {
-                std::fs::remove_file(&new_path)?;
+            if overwrite_dest {
+                remove_existing(&new_path)?;
             }
             if symlink_instead {
                 std::os::unix::fs::symlink(entry.path(), new_path)?;
@@ -55,6 +72,43 @@ pub fn copy_dir_recursive(
     Ok(())
 }
 
+// Total size of the files under a folder, without following symlinks
+pub fn dir_size(path: &PathBuf) -> u64 {
+    walkdir::WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+pub fn sha256_file(path: &PathBuf) -> Result<String, Box<dyn Error>> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+pub fn human_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", units[unit]),
+    }
+}
+
 pub fn get_rootpath_handler(handler: &Handler) -> Result<String, Box<dyn Error>> {
     if let Some(value) = find_saved_path(&handler.uid) {
         return value;
@@ -91,6 +145,22 @@ pub fn get_rootpath_handler(handler: &Handler) -> Result<String, Box<dyn Error>>
     Ok(result)
 }
 
+// All Steam library folders, starting with the main Steam install
+pub fn steam_library_paths() -> Vec<PathBuf> {
+    let mut out = vec![PATH_STEAM.clone()];
+    if let Ok(steamdir) = steamlocate::SteamDir::locate()
+        && let Ok(libraries) = steamdir.libraries()
+    {
+        for library in libraries.flatten() {
+            let path = library.path().to_path_buf();
+            if !out.contains(&path) {
+                out.push(path);
+            }
+        }
+    }
+    out
+}
+
 pub fn get_rootpath(uid: &str) -> Result<String, Box<dyn Error>> {
     if let Some(value) = find_saved_path(uid) {
         return value;
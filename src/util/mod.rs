@@ -5,15 +5,22 @@ mod sys;
 mod updates;
 
 // Re-export functions from profiles
-pub use profiles::{create_gamesave, create_profile, remove_guest_profiles, scan_profiles};
+pub use profiles::{
+    PROFILE_COLORS, ProfileMeta, copy_profile_files, create_gamesave, create_profile,
+    delete_profile, duplicate_profile, is_valid_profile_name, offer_guest_promotions,
+    remove_guest_profiles, rename_profile, scan_guest_profiles, scan_profiles,
+};
 
 // Re-export functions from filesystem
-pub use filesystem::{SanitizePath, copy_dir_recursive, get_rootpath, get_rootpath_handler};
+pub use filesystem::{
+    SanitizePath, copy_dir_recursive, dir_size, get_rootpath, get_rootpath_handler, human_size,
+    sha256_file, steam_library_paths,
+};
 
 // Re-export functions from launcher
 pub use sys::{
-    get_instance_resolution, get_screen_resolution, kwin_dbus_start_script,
-    kwin_dbus_unload_script, msg, yesno,
+    get_instance_resolution, get_screen_resolution, kwin_dbus_available, kwin_dbus_start_script,
+    kwin_dbus_unload_script, msg, set_progress, take_progress, yesno,
 };
 
 // Re-export functions from updates
@@ -1,8 +1,15 @@
-use rand::prelude::*;
+use dialog::DialogBox;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
 use std::path::PathBuf;
 
-use crate::util::filesystem::copy_dir_recursive;
+use crate::goldberg::{GoldbergProfile, unique_steam_id};
+use crate::handler::scan_handlers;
+use crate::snapshots::path_snapshots;
+use crate::util::filesystem::{copy_dir_recursive, dir_size, sha256_file};
+use crate::util::sys::{msg, yesno};
 use crate::{handler::Handler, paths::*};
 
 // Makes a folder and sets up Goldberg Steam Emu profile for Steam games
@@ -12,21 +19,21 @@ pub fn create_profile(name: &str) -> Result<(), std::io::Error> {
     }
 
     println!("Creating profile {name}");
-    let path_steam = PATH_PARTY.join(format!("profiles/{name}/steam/settings"));
-    std::fs::create_dir_all(path_steam.clone())?;
-
-    let steam_id = format!("{:017}", rand::rng().random_range(u32::MIN..u32::MAX));
-    let usersettings = format!(
-        "[user::general]\naccount_name={name}\naccount_steamid={steam_id}\nlanguage=english\nip_country=US"
-    );
-    std::fs::write(path_steam.join("configs.user.ini"), usersettings)?;
+    let profile = GoldbergProfile {
+        name: name.to_string(),
+        steam_id: unique_steam_id(name),
+        language: "english".to_string(),
+        country: "US".to_string(),
+    };
+    profile.save(name)?;
 
     println!("Created successfully");
     Ok(())
 }
 
-// Creates the "game save" folder for per-profile game data to go into
-pub fn create_gamesave(name: &str, h: &Handler) -> Result<(), Box<dyn Error>> {
+// Creates the "game save" folder for per-profile game data to go into. Returns false if the
+// profile already had one.
+pub fn create_gamesave(name: &str, h: &Handler) -> Result<bool, Box<dyn Error>> {
     let path_gamesave = PATH_PARTY
         .join("profiles")
         .join(name)
@@ -35,7 +42,7 @@ pub fn create_gamesave(name: &str, h: &Handler) -> Result<(), Box<dyn Error>> {
 
     if path_gamesave.exists() {
         println!("{} already has save for {}, continuing...", name, h.uid);
-        return Ok(());
+        return Ok(false);
     }
     println!("Creating game save {} for {}", h.uid, name);
 
@@ -53,6 +60,9 @@ pub fn create_gamesave(name: &str, h: &Handler) -> Result<(), Box<dyn Error>> {
     if h.linux_unique_config {
         std::fs::create_dir_all(path_gamesave.join("_config"))?;
     }
+    if !h.flatpak.is_empty() {
+        std::fs::create_dir_all(path_gamesave.join("_flatpak"))?;
+    }
 
     for path in &h.game_unique_paths {
         if path.is_empty() {
@@ -78,7 +88,7 @@ pub fn create_gamesave(name: &str, h: &Handler) -> Result<(), Box<dyn Error>> {
     }
 
     println!("Save data directories created successfully");
-    Ok(())
+    Ok(true)
 }
 
 // Gets a vector of all available profiles.
@@ -91,6 +101,10 @@ pub fn scan_profiles(include_guest: bool) -> Vec<String> {
             if let Ok(entry) = entry {
                 if entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
                     if let Some(name) = entry.file_name().to_str() {
+                        // Guests aren't picked by name, even when they're being kept around
+                        if name.starts_with('.') {
+                            continue;
+                        }
                         out.push(name.to_string());
                     }
                 }
@@ -107,6 +121,107 @@ pub fn scan_profiles(include_guest: bool) -> Vec<String> {
     out
 }
 
+// Guest profiles currently on disk, with the dot in front
+pub fn scan_guest_profiles() -> Vec<String> {
+    let mut out: Vec<String> = std::fs::read_dir(PATH_PARTY.join("profiles"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.file_type().is_ok_and(|ft| ft.is_dir()))
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|name| name.starts_with('.'))
+                .collect()
+        })
+        .unwrap_or_default();
+    out.sort();
+    out
+}
+
+// Whether a save file is still what create_gamesave copied in from the handler
+fn is_seeded(file: &PathBuf, seed: &PathBuf) -> bool {
+    match (sha256_file(file), sha256_file(seed)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Whether a profile has saved anything, not counting the Goldberg settings every profile gets or
+// the handlers' copy_to_profilesave files
+fn has_progress(name: &str, handlers: &[Handler]) -> bool {
+    let path = PATH_PARTY.join("profiles").join(name);
+    let steam: u64 = std::fs::read_dir(path.join("steam"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.file_name() != "settings")
+                .map(|e| dir_size(&e.path()))
+                .sum()
+        })
+        .unwrap_or(0);
+    if steam > 0 {
+        return true;
+    }
+
+    let Ok(games) = std::fs::read_dir(path.join("saves")) else {
+        return false;
+    };
+    games.flatten().any(|game| {
+        let uid = game.file_name().to_string_lossy().to_string();
+        let seed = handlers
+            .iter()
+            .find(|h| h.uid == uid)
+            .map(|h| h.path_handler.join("copy_to_profilesave"));
+        walkdir::WalkDir::new(game.path())
+            .into_iter()
+            .flatten()
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| e.metadata().is_ok_and(|m| m.len() > 0))
+            .any(|e| {
+                let rel = e.path().strip_prefix(game.path()).unwrap_or(e.path());
+                !seed
+                    .as_ref()
+                    .is_some_and(|seed| is_seeded(&e.path().to_path_buf(), &seed.join(rel)))
+            })
+    })
+}
+
+// Asks whether to keep each guest that made any progress as a regular profile. The guest keeps
+// its Steam ID, so games still recognize the saves, and rename_profile swaps its Goldberg name
+// (the guest's folder name, dot included) for the new one.
+pub fn offer_guest_promotions() {
+    let guests = scan_guest_profiles();
+    if guests.is_empty() {
+        return;
+    }
+    let handlers = scan_handlers();
+    for guest in guests {
+        if !has_progress(&guest, &handlers) {
+            continue;
+        }
+        let guest_name = guest.trim_start_matches('.');
+        if !yesno(
+            "Keep Guest Progress?",
+            &format!(
+                "{guest_name} played as a guest. Keep this guest's progress as a new profile? Games will show the new profile's name, and keep recognizing the saves."
+            ),
+        ) {
+            continue;
+        }
+        while let Some(name) =
+            dialog::Input::new("Enter a name for the new profile (must be alphanumeric):")
+                .title("Keep Guest Progress")
+                .default(guest_name)
+                .show()
+                .expect("Could not display dialog box")
+        {
+            match rename_profile(&guest, &name) {
+                Ok(()) => break,
+                Err(err) => msg("Error", &format!("Couldn't create profile: {}", err)),
+            }
+        }
+    }
+}
+
 pub fn remove_guest_profiles() -> Result<(), Box<dyn Error>> {
     let path_profiles = PATH_PARTY.join("profiles");
     let entries = std::fs::read_dir(&path_profiles)?;
@@ -124,3 +239,124 @@ pub fn remove_guest_profiles() -> Result<(), Box<dyn Error>> {
     }
     Ok(())
 }
+
+// Colours a profile can be marked with, picked from a fixed set so they stay easy to tell apart
+pub static PROFILE_COLORS: [[u8; 3]; 8] = [
+    [231, 76, 60],
+    [52, 152, 219],
+    [46, 204, 113],
+    [241, 196, 15],
+    [155, 89, 182],
+    [230, 126, 34],
+    [26, 188, 156],
+    [236, 112, 160],
+];
+
+// PartyDeck's own settings for a profile, kept in profiles/<name>/profile.json.
+// The avatar is Goldberg's account_avatar, so games see the same picture.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProfileMeta {
+    pub color: [u8; 3],
+}
+
+impl ProfileMeta {
+    pub fn load(name: &str) -> Self {
+        let path = PATH_PARTY.join("profiles").join(name).join("profile.json");
+        if let Ok(file) = File::open(path)
+            && let Ok(meta) = serde_json::from_reader::<_, ProfileMeta>(BufReader::new(file))
+        {
+            return meta;
+        }
+        // Profiles without a colour yet get one based on their name
+        let i = name.bytes().map(|b| b as usize).sum::<usize>() % PROFILE_COLORS.len();
+        Self {
+            color: PROFILE_COLORS[i],
+        }
+    }
+
+    pub fn save(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        let path = PATH_PARTY.join("profiles").join(name).join("profile.json");
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+pub fn is_valid_profile_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(char::is_alphanumeric)
+}
+
+fn check_new_profile_name(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if !is_valid_profile_name(name) {
+        return Err("Profile names must be alphanumeric".into());
+    }
+    let path = PATH_PARTY.join("profiles").join(name);
+    if path.exists() {
+        return Err(format!("A profile named {name} already exists").into());
+    }
+    Ok(path)
+}
+
+// The Steam ID stays the same, so games keep recognizing the profile's saves
+pub fn rename_profile(old: &str, new: &str) -> Result<(), Box<dyn Error>> {
+    let path_new = check_new_profile_name(new)?;
+    std::fs::rename(PATH_PARTY.join("profiles").join(old), &path_new)?;
+    if path_snapshots(old).exists() {
+        std::fs::rename(path_snapshots(old), path_snapshots(new))?;
+    }
+
+    let mut goldberg = GoldbergProfile::load(new);
+    if goldberg.name == old {
+        goldberg.name = new.to_string();
+        goldberg.save(new)?;
+    }
+    Ok(())
+}
+
+// Copies a profile folder except for its per-profile Wine prefixes, which are big and get
+// recreated when needed
+pub fn copy_profile_files(src: &PathBuf, dest: &PathBuf) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(src)?.flatten() {
+        if entry.file_name() == "pfx" {
+            continue;
+        }
+        let path_dest = dest.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            std::fs::create_dir_all(&path_dest)?;
+            copy_dir_recursive(&entry.path(), &path_dest, false, false)?;
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(std::fs::read_link(entry.path())?, &path_dest)?;
+        } else {
+            std::fs::copy(entry.path(), &path_dest)?;
+        }
+    }
+    Ok(())
+}
+
+// Copies everything including saves, but gives the copy its own Steam ID so the two profiles
+// can play together
+pub fn duplicate_profile(src: &str, new: &str) -> Result<(), Box<dyn Error>> {
+    let path_new = check_new_profile_name(new)?;
+    copy_profile_files(&PATH_PARTY.join("profiles").join(src), &path_new)?;
+
+    let mut goldberg = GoldbergProfile::load(new);
+    if goldberg.name == src {
+        goldberg.name = new.to_string();
+    }
+    goldberg.steam_id = unique_steam_id(new);
+    goldberg.save(new)?;
+    Ok(())
+}
+
+pub fn delete_profile(name: &str) -> Result<(), Box<dyn Error>> {
+    if !is_valid_profile_name(name) {
+        return Err("Invalid profile name".into());
+    }
+    std::fs::remove_dir_all(PATH_PARTY.join("profiles").join(name))?;
+    if path_snapshots(name).exists() {
+        std::fs::remove_dir_all(path_snapshots(name))?;
+    }
+    Ok(())
+}
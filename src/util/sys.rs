@@ -1,6 +1,7 @@
 use dialog::{Choice, DialogBox};
 use std::error::Error;
 use std::path::PathBuf;
+use std::sync::Mutex;
 use x11rb::connection::Connection;
 
 pub fn msg(title: &str, contents: &str) {
@@ -16,6 +17,20 @@ pub fn yesno(title: &str, contents: &str) -> bool {
     false
 }
 
+static PROGRESS: Mutex<Option<String>> = Mutex::new(None);
+
+// Lets a background task replace the loading message shown by the GUI
+pub fn set_progress(text: &str) {
+    println!("{text}");
+    if let Ok(mut progress) = PROGRESS.lock() {
+        *progress = Some(text.to_string());
+    }
+}
+
+pub fn take_progress() -> Option<String> {
+    PROGRESS.lock().ok().and_then(|mut progress| progress.take())
+}
+
 pub fn get_screen_resolution() -> (u32, u32) {
     if let Ok(conn) = x11rb::connect(None) {
         let screen = &conn.0.setup().roots[0];
@@ -103,6 +118,20 @@ pub fn kwin_dbus_start_script(file: PathBuf) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+// Checks that KWin's scripting interface can be reached, without loading anything
+pub fn kwin_dbus_available() -> Result<(), Box<dyn Error>> {
+    let conn = zbus::blocking::Connection::session()?;
+    let proxy = zbus::blocking::Proxy::new(
+        &conn,
+        "org.kde.KWin",
+        "/Scripting",
+        "org.kde.kwin.Scripting",
+    )?;
+
+    let _: bool = proxy.call("isScriptLoaded", &("splitscreen"))?;
+    Ok(())
+}
+
 pub fn kwin_dbus_unload_script() -> Result<(), Box<dyn Error>> {
     println!("Unloading splitscreen script...");
     let conn = zbus::blocking::Connection::session()?;